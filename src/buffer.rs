@@ -0,0 +1,547 @@
+use opentelemetry::{
+    sdk::export::trace::SpanData,
+    trace::{SpanId, TraceId},
+};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Spans of one trace grouped by their parent span id.
+pub(crate) type Trace = HashMap<SpanId, Vec<SpanData>>;
+
+/// What happens to traces, which are removed from a full buffer.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EvictionPolicy {
+    /// Print evicted traces incomplete.
+    #[default]
+    Print,
+    /// Drop evicted traces. The number of dropped traces and spans is printed before the next
+    /// printed trace and on shutdown.
+    Drop,
+}
+
+/// Options, which control how long spans are buffered.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct BufferOptions {
+    /// Traces are printed incomplete once their first span was buffered this long ago.
+    pub(crate) max_trace_age: Option<Duration>,
+    /// Maximum number of buffered traces. The oldest trace is evicted to make space for new ones.
+    pub(crate) max_traces: Option<usize>,
+    /// Maximum number of buffered spans per trace. A trace is evicted when it has too many spans.
+    pub(crate) max_spans_per_trace: Option<usize>,
+    pub(crate) eviction_policy: EvictionPolicy,
+    /// Traces are printed this long after their root span arrived, to include spans ending after
+    /// the root. Spans arriving within another grace period after the trace was printed are
+    /// printed separately. If not set, traces are printed as soon as their root span arrives.
+    pub(crate) grace_period: Option<Duration>,
+}
+
+/// Whether the span is the root of a trace, i.e. it doesn't have a parent or its parent is in
+/// another process. Exported spans don't tell whether their parent is remote, so it has to be
+/// recorded when the span starts.
+fn is_root(span_data: &SpanData, has_remote_parent: bool) -> bool {
+    span_data.parent_span_id == SpanId::INVALID || has_remote_parent
+}
+
+#[derive(Debug)]
+struct BufferedTrace {
+    first_buffered: Instant,
+    span_count: usize,
+    /// When the first root span of the trace arrived.
+    root_arrived: Option<Instant>,
+    spans: Trace,
+}
+
+impl BufferedTrace {
+    /// Remove the span with its descendants from the trace.
+    fn take_descendants(&mut self, span_id: SpanId, subtree: &mut Trace) {
+        if let Some(children) = self.spans.remove(&span_id) {
+            self.span_count -= children.len();
+            for child in &children {
+                self.take_descendants(child.span_context.span_id(), subtree);
+            }
+            subtree.insert(span_id, children);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct PrintedTrace {
+    printed: Instant,
+    span_ids: HashSet<SpanId>,
+}
+
+/// Number of traces and spans, which were dropped because the buffer was full.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub(crate) struct Dropped {
+    pub(crate) traces: usize,
+    pub(crate) spans: usize,
+}
+
+/// Spans, which are waiting for the root span of their trace.
+#[derive(Debug, Default)]
+pub(crate) struct TraceBuffer {
+    traces: HashMap<TraceId, BufferedTrace>,
+    /// Buffered traces in the order their root span arrived, so that finding the traces whose
+    /// grace period ended doesn't scan the whole buffer. Entries of traces, which were removed in
+    /// the meantime, are skipped.
+    rooted: VecDeque<(Instant, TraceId)>,
+    options: BufferOptions,
+    dropped: Dropped,
+    printed: HashMap<TraceId, PrintedTrace>,
+}
+
+impl TraceBuffer {
+    pub(crate) fn new(options: BufferOptions) -> Self {
+        Self {
+            traces: HashMap::new(),
+            rooted: VecDeque::new(),
+            options,
+            dropped: Dropped::default(),
+            printed: HashMap::new(),
+        }
+    }
+
+    /// Buffer the span. Returns traces, which were evicted to stay within the limits and should
+    /// be printed incomplete.
+    pub(crate) fn push(
+        &mut self,
+        span_data: SpanData,
+        has_remote_parent: bool,
+        now: Instant,
+    ) -> Vec<(TraceId, Trace)> {
+        self.forget_printed(now);
+        let trace_id = span_data.span_context.trace_id();
+        let mut evicted = Vec::new();
+
+        let is_trace_full = self.traces.get(&trace_id).is_some_and(|trace| {
+            self.options
+                .max_spans_per_trace
+                .is_some_and(|max| trace.span_count >= max)
+        });
+        if is_trace_full {
+            evicted.extend(self.evict(trace_id));
+        }
+        if let Some(max_traces) = self.options.max_traces {
+            while !self.traces.contains_key(&trace_id) && self.traces.len() >= max_traces.max(1) {
+                let oldest = self
+                    .traces
+                    .iter()
+                    .min_by_key(|(_, trace)| trace.first_buffered)
+                    .map(|(trace_id, _)| *trace_id)
+                    .expect("buffer must not be empty");
+                evicted.extend(self.evict(oldest));
+            }
+        }
+
+        let trace = self
+            .traces
+            .entry(trace_id)
+            .or_insert_with(|| BufferedTrace {
+                first_buffered: now,
+                span_count: 0,
+                root_arrived: None,
+                spans: HashMap::new(),
+            });
+        trace.span_count += 1;
+        let parent_span_id = if is_root(&span_data, has_remote_parent) {
+            if trace.root_arrived.is_none() {
+                trace.root_arrived = Some(now);
+                self.rooted.push_back((now, trace_id));
+            }
+            SpanId::INVALID
+        } else {
+            span_data.parent_span_id
+        };
+        trace
+            .spans
+            .entry(parent_span_id)
+            .or_default()
+            .push(span_data);
+        evicted
+    }
+
+    /// Remove the trace according to the eviction policy.
+    fn evict(&mut self, trace_id: TraceId) -> Option<(TraceId, Trace)> {
+        let trace = self.traces.remove(&trace_id)?;
+        match self.options.eviction_policy {
+            EvictionPolicy::Print => Some((trace_id, trace.spans)),
+            EvictionPolicy::Drop => {
+                self.dropped.traces += 1;
+                self.dropped.spans += trace.span_count;
+                None
+            }
+        }
+    }
+
+    /// Whether the parent of the span was already printed, i.e. the span arrived after the grace
+    /// period of its trace.
+    pub(crate) fn is_late(&self, span_data: &SpanData) -> bool {
+        self.printed
+            .get(&span_data.span_context.trace_id())
+            .is_some_and(|printed| printed.span_ids.contains(&span_data.parent_span_id))
+    }
+
+    /// Remove the buffered descendants of a late span. The returned trace has the late span as
+    /// its root.
+    pub(crate) fn take_late(&mut self, span_data: SpanData, now: Instant) -> Trace {
+        self.forget_printed(now);
+        let trace_id = span_data.span_context.trace_id();
+        let mut late = Trace::new();
+        if let Some(trace) = self.traces.get_mut(&trace_id) {
+            trace.take_descendants(span_data.span_context.span_id(), &mut late);
+            if trace.span_count == 0 {
+                self.traces.remove(&trace_id);
+            }
+        }
+        late.insert(SpanId::INVALID, vec![span_data]);
+        self.remember_printed(trace_id, &late, now);
+        late
+    }
+
+    /// Remove traces, whose grace period ended, oldest first.
+    pub(crate) fn take_ready(&mut self, now: Instant) -> Vec<(TraceId, Trace)> {
+        let grace_period = self.options.grace_period.unwrap_or_default();
+        self.forget_printed(now);
+
+        let mut ready = Vec::new();
+        while let Some(&(root_arrived, trace_id)) = self.rooted.front() {
+            if now.saturating_duration_since(root_arrived) < grace_period {
+                break;
+            }
+            self.rooted.pop_front();
+            let is_buffered = self
+                .traces
+                .get(&trace_id)
+                .is_some_and(|trace| trace.root_arrived == Some(root_arrived));
+            if is_buffered {
+                let trace = self.take(trace_id);
+                self.remember_printed(trace_id, &trace, now);
+                ready.push((trace_id, trace));
+            }
+        }
+        ready
+    }
+
+    /// Remember the spans of a printed trace for another grace period to recognize late spans.
+    /// The same limits as for buffered traces apply.
+    fn remember_printed(&mut self, trace_id: TraceId, trace: &Trace, now: Instant) {
+        // Late spans are only recognized with a grace period. Without one, spans ending after the
+        // root are common enough, that their traces are not worth remembering.
+        if self.options.grace_period.is_none() {
+            return;
+        }
+        if let Some(max_traces) = self.options.max_traces {
+            while !self.printed.contains_key(&trace_id) && self.printed.len() >= max_traces.max(1) {
+                let oldest = self
+                    .printed
+                    .iter()
+                    .min_by_key(|(_, printed)| printed.printed)
+                    .map(|(trace_id, _)| *trace_id)
+                    .expect("printed traces must not be empty");
+                self.printed.remove(&oldest);
+            }
+        }
+
+        let printed = self
+            .printed
+            .entry(trace_id)
+            .or_insert_with(|| PrintedTrace {
+                printed: now,
+                span_ids: HashSet::new(),
+            });
+        printed.printed = now;
+        let max_spans = self.options.max_spans_per_trace.unwrap_or(usize::MAX);
+        let remaining = max_spans.saturating_sub(printed.span_ids.len());
+        printed.span_ids.extend(
+            trace
+                .values()
+                .flatten()
+                .map(|span_data| span_data.span_context.span_id())
+                .take(remaining),
+        );
+    }
+
+    /// Forget printed traces, whose spans can't be late anymore.
+    fn forget_printed(&mut self, now: Instant) {
+        let window = self.options.grace_period.unwrap_or_default();
+        self.printed
+            .retain(|_, printed| now.saturating_duration_since(printed.printed) < window);
+    }
+
+    /// Number of traces and spans dropped since the last call.
+    pub(crate) fn take_dropped(&mut self) -> Dropped {
+        std::mem::take(&mut self.dropped)
+    }
+
+    /// Remove all buffered spans of the trace.
+    pub(crate) fn take(&mut self, trace_id: TraceId) -> Trace {
+        self.traces
+            .remove(&trace_id)
+            .map(|trace| trace.spans)
+            .unwrap_or_default()
+    }
+
+    /// Remove traces, which reached the maximum age, oldest first.
+    pub(crate) fn take_expired(&mut self, now: Instant) -> Vec<(TraceId, Trace)> {
+        let max_trace_age = match self.options.max_trace_age {
+            Some(max_trace_age) => max_trace_age,
+            None => return Vec::new(),
+        };
+        let mut expired = self
+            .traces
+            .iter()
+            .filter(|(_, trace)| {
+                // Traces with a root are printed after the grace period.
+                trace.root_arrived.is_none()
+                    && now.saturating_duration_since(trace.first_buffered) >= max_trace_age
+            })
+            .map(|(trace_id, trace)| (*trace_id, trace.first_buffered))
+            .collect::<Vec<_>>();
+        expired.sort_by_key(|(_, first_buffered)| *first_buffered);
+        expired
+            .into_iter()
+            .map(|(trace_id, _)| (trace_id, self.take(trace_id)))
+            .collect()
+    }
+
+    /// Remove all traces, oldest first. Spans arriving later, whose parent is in one of the
+    /// traces, are recognized as late spans.
+    pub(crate) fn take_all(&mut self, now: Instant) -> Vec<(TraceId, Trace)> {
+        self.rooted.clear();
+        let mut traces = self
+            .traces
+            .drain()
+            .map(|(trace_id, trace)| (trace.first_buffered, trace_id, trace.spans))
+            .collect::<Vec<_>>();
+        traces.sort_by_key(|(first_buffered, _, _)| *first_buffered);
+        traces
+            .into_iter()
+            .map(|(_, trace_id, spans)| {
+                self.remember_printed(trace_id, &spans, now);
+                (trace_id, spans)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{span, span_id, trace_id};
+    use opentelemetry::trace::SpanContext;
+
+    fn span_in_trace(trace: u128, id: u64) -> SpanData {
+        let mut span_data = span(id, 1, "span", vec![]);
+        span_data.span_context = SpanContext::new(
+            trace_id(trace),
+            span_data.span_context.span_id(),
+            Default::default(),
+            false,
+            Default::default(),
+        );
+        span_data
+    }
+
+    fn trace_ids(traces: Vec<(TraceId, Trace)>) -> Vec<TraceId> {
+        traces.into_iter().map(|(trace_id, _)| trace_id).collect()
+    }
+
+    #[test]
+    fn expiry() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::new(BufferOptions {
+            max_trace_age: Some(Duration::from_secs(10)),
+            ..Default::default()
+        });
+        buffer.push(span_in_trace(2, 1), false, start + Duration::from_secs(1));
+        buffer.push(span_in_trace(1, 2), false, start);
+        buffer.push(span_in_trace(3, 3), false, start + Duration::from_secs(5));
+        buffer.push(span_in_trace(1, 4), false, start + Duration::from_secs(8));
+
+        assert!(buffer
+            .take_expired(start + Duration::from_secs(9))
+            .is_empty());
+        let expired = buffer.take_expired(start + Duration::from_secs(11));
+        assert_eq!(vec![trace_id(1), trace_id(2)], trace_ids(expired));
+        assert_eq!(vec![trace_id(3)], trace_ids(buffer.take_all(start)));
+    }
+
+    #[test]
+    fn max_traces() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::new(BufferOptions {
+            max_traces: Some(2),
+            ..Default::default()
+        });
+        assert!(buffer.push(span_in_trace(1, 1), false, start).is_empty());
+        assert!(buffer
+            .push(span_in_trace(2, 2), false, start + Duration::from_secs(1))
+            .is_empty());
+        assert!(buffer
+            .push(span_in_trace(1, 3), false, start + Duration::from_secs(2))
+            .is_empty());
+        let evicted = buffer.push(span_in_trace(3, 4), false, start + Duration::from_secs(3));
+        assert_eq!(vec![trace_id(1)], trace_ids(evicted));
+        assert_eq!(Dropped::default(), buffer.take_dropped());
+        assert_eq!(
+            vec![trace_id(2), trace_id(3)],
+            trace_ids(buffer.take_all(start))
+        );
+    }
+
+    #[test]
+    fn max_spans_per_trace() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::new(BufferOptions {
+            max_spans_per_trace: Some(2),
+            eviction_policy: EvictionPolicy::Drop,
+            ..Default::default()
+        });
+        for id in 1..=5 {
+            assert!(buffer.push(span_in_trace(1, id), false, start).is_empty());
+        }
+        assert_eq!(
+            Dropped {
+                traces: 2,
+                spans: 4
+            },
+            buffer.take_dropped()
+        );
+        assert_eq!(Dropped::default(), buffer.take_dropped());
+        let traces = buffer.take_all(start);
+        assert_eq!(1, traces[0].1.values().flatten().count());
+    }
+
+    #[test]
+    fn grace_period_and_late_spans() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::new(BufferOptions {
+            grace_period: Some(Duration::from_secs(1)),
+            ..Default::default()
+        });
+        let child = |id, parent| span(id, parent, "child", vec![]);
+        let root = span(1, 0, "root", vec![]);
+        let second_root = span(2, 0, "root", vec![]);
+
+        buffer.push(root, false, start);
+        buffer.push(child(3, 1), false, start + Duration::from_millis(500));
+        buffer.push(second_root, false, start + Duration::from_millis(600));
+        assert!(buffer
+            .take_ready(start + Duration::from_millis(900))
+            .is_empty());
+        let ready = buffer.take_ready(start + Duration::from_secs(1));
+        assert_eq!(vec![trace_id(1)], trace_ids(ready.clone()));
+        assert_eq!(2, ready[0].1[&SpanId::INVALID].len());
+        assert_eq!(1, ready[0].1[&span_id(1)].len());
+
+        // Span 5 is a child of the late span 4 and arrives before it.
+        let grandchild = child(5, 4);
+        assert!(!buffer.is_late(&grandchild));
+        buffer.push(grandchild, false, start + Duration::from_millis(1500));
+        let late = child(4, 3);
+        assert!(buffer.is_late(&late));
+        let late = buffer.take_late(late, start + Duration::from_millis(1500));
+        assert_eq!(span_id(4), late[&SpanId::INVALID][0].span_context.span_id());
+        assert_eq!(span_id(5), late[&span_id(4)][0].span_context.span_id());
+        assert!(buffer.take_all(start).is_empty());
+
+        // Printed traces are forgotten after another grace period.
+        buffer.push(child(7, 8), false, start + Duration::from_secs(3));
+        assert!(!buffer.is_late(&child(6, 3)));
+    }
+
+    #[test]
+    fn grace_period_of_evicted_trace() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::new(BufferOptions {
+            grace_period: Some(Duration::from_secs(1)),
+            max_traces: Some(1),
+            ..Default::default()
+        });
+        let root = |trace| {
+            let mut span_data = span_in_trace(trace, 1);
+            span_data.parent_span_id = SpanId::INVALID;
+            span_data
+        };
+
+        buffer.push(root(1), false, start);
+        let evicted = buffer.push(
+            span_in_trace(2, 2),
+            false,
+            start + Duration::from_millis(500),
+        );
+        assert_eq!(vec![trace_id(1)], trace_ids(evicted));
+        let evicted = buffer.push(root(1), false, start + Duration::from_millis(800));
+        assert_eq!(vec![trace_id(2)], trace_ids(evicted));
+
+        // The grace period starts again with the root of the new trace.
+        assert!(buffer.take_ready(start + Duration::from_secs(1)).is_empty());
+        let ready = buffer.take_ready(start + Duration::from_millis(1800));
+        assert_eq!(vec![trace_id(1)], trace_ids(ready));
+    }
+
+    #[test]
+    fn printed_traces_limits() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::new(BufferOptions {
+            grace_period: Some(Duration::from_secs(1)),
+            max_traces: Some(1),
+            max_spans_per_trace: Some(2),
+            ..Default::default()
+        });
+        let root = |trace, id| {
+            let mut span_data = span_in_trace(trace, id);
+            span_data.parent_span_id = SpanId::INVALID;
+            span_data
+        };
+        let child = |trace, id, parent| {
+            let mut span_data = span_in_trace(trace, id);
+            span_data.parent_span_id = span_id(parent);
+            span_data
+        };
+        let remembered =
+            |buffer: &TraceBuffer, trace, parent| buffer.is_late(&child(trace, 9, parent));
+
+        buffer.push(root(1, 1), false, start);
+        buffer.push(child(1, 2, 1), false, start);
+        assert_eq!(1, buffer.take_ready(start + Duration::from_secs(1)).len());
+        buffer.push(root(2, 4), false, start + Duration::from_secs(1));
+        assert!(remembered(&buffer, 1, 1) && remembered(&buffer, 1, 2));
+        // The printed trace is full, so the id of the late span isn't remembered.
+        buffer.take_late(child(1, 3, 2), start + Duration::from_millis(1900));
+        assert!(!remembered(&buffer, 1, 3));
+
+        // Printing another trace evicts the first one, although its spans could still be late.
+        assert_eq!(1, buffer.take_ready(start + Duration::from_secs(2)).len());
+        assert!(remembered(&buffer, 2, 4));
+        assert!(!remembered(&buffer, 1, 1) && !remembered(&buffer, 1, 2));
+    }
+
+    #[test]
+    fn remote_parent() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::default();
+        buffer.push(span(2, 1, "local parent", vec![]), false, start);
+        buffer.push(span(3, 4, "remote parent", vec![]), true, start);
+        let ready = buffer.take_ready(start);
+        assert_eq!(vec![trace_id(1)], trace_ids(ready.clone()));
+        assert_eq!(
+            span_id(3),
+            ready[0].1[&SpanId::INVALID][0].span_context.span_id()
+        );
+        assert_eq!(1, ready[0].1[&span_id(1)].len());
+    }
+
+    #[test]
+    fn no_expiry_without_max_age() {
+        let start = Instant::now();
+        let mut buffer = TraceBuffer::default();
+        buffer.push(span_in_trace(1, 1), false, start);
+        assert!(buffer
+            .take_expired(start + Duration::from_secs(3600))
+            .is_empty());
+        assert_eq!(vec![trace_id(1)], trace_ids(buffer.take_all(start)));
+    }
+}
@@ -0,0 +1,210 @@
+use opentelemetry::trace::SpanKind;
+use std::ffi::OsString;
+use termcolor::{Color, ColorChoice, ColorSpec};
+
+/// Controls whether the output contains colors.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum ColorMode {
+    /// Use colors if the output is a terminal, unless the `NO_COLOR` environment variable is set.
+    /// Setting `CLICOLOR_FORCE` forces colors, even if the output is not a terminal.
+    #[default]
+    Auto,
+    /// Always use colors. On Windows this uses the console API if possible.
+    Always,
+    /// Always use colors and emit them as ANSI escape sequences, even on Windows.
+    AlwaysAnsi,
+    /// Never use colors.
+    Never,
+}
+
+impl ColorMode {
+    pub(crate) fn resolve(self, is_terminal: bool) -> ColorChoice {
+        self.resolve_with_env(is_terminal, |key| std::env::var_os(key))
+    }
+
+    fn resolve_with_env(
+        self,
+        is_terminal: bool,
+        env: impl Fn(&str) -> Option<OsString>,
+    ) -> ColorChoice {
+        match self {
+            ColorMode::Always => ColorChoice::Always,
+            ColorMode::AlwaysAnsi => ColorChoice::AlwaysAnsi,
+            ColorMode::Never => ColorChoice::Never,
+            ColorMode::Auto => {
+                // See https://no-color.org and https://bixense.com/clicolors/
+                let no_color = env("NO_COLOR").is_some_and(|v| !v.is_empty());
+                let force = env("CLICOLOR_FORCE").is_some_and(|v| !v.is_empty() && v != "0");
+                let dumb = env("TERM").is_some_and(|v| v == "dumb");
+                if no_color {
+                    ColorChoice::Never
+                } else if force {
+                    ColorChoice::Always
+                } else if is_terminal && !dumb {
+                    ColorChoice::Auto
+                } else {
+                    ColorChoice::Never
+                }
+            }
+        }
+    }
+}
+
+/// Styles used to print traces.
+///
+/// Every part of a row has its own style. If a style is empty, the part uses the style of the row
+/// instead, which is `warning` or `error` for spans with a warning or error status and `event` or
+/// `exception` for events.
+#[derive(Clone, Debug)]
+#[non_exhaustive]
+pub struct Theme {
+    /// Kind badge of client spans (`CL`).
+    pub client: ColorSpec,
+    /// Kind badge of server spans (`SE`).
+    pub server: ColorSpec,
+    /// Kind badge of producer spans (`PR`).
+    pub producer: ColorSpec,
+    /// Kind badge of consumer spans (`CO`).
+    pub consumer: ColorSpec,
+    /// Kind badge of internal spans (`IN`).
+    pub internal: ColorSpec,
+    /// Duration column.
+    pub duration: ColorSpec,
+    /// Timing bar.
+    pub timing: ColorSpec,
+    /// Events.
+    pub event: ColorSpec,
+    /// Exception events.
+    pub exception: ColorSpec,
+    /// Stack trace frames of exception events.
+    pub frame: ColorSpec,
+    /// Stack trace frames, which match [`StackTraceOptions::highlight`].
+    ///
+    /// [`StackTraceOptions::highlight`]: crate::StackTraceOptions::highlight
+    pub own_frame: ColorSpec,
+    /// Trace header and footer.
+    pub header: ColorSpec,
+    /// Spans with a warning status, e.g. HTTP 4xx.
+    pub warning: ColorSpec,
+    /// Spans with an error status, e.g. HTTP 5xx or `StatusCode::Error`.
+    pub error: ColorSpec,
+}
+
+impl Default for Theme {
+    /// Default theme, which only highlights warnings, errors and exceptions.
+    fn default() -> Self {
+        Self {
+            exception: fg(Color::Red),
+            own_frame: bold(fg(Color::Red)),
+            warning: fg(Color::Yellow),
+            error: fg(Color::Red),
+            ..Self::unstyled()
+        }
+    }
+}
+
+impl Theme {
+    fn unstyled() -> Self {
+        Self {
+            client: ColorSpec::new(),
+            server: ColorSpec::new(),
+            producer: ColorSpec::new(),
+            consumer: ColorSpec::new(),
+            internal: ColorSpec::new(),
+            duration: ColorSpec::new(),
+            timing: ColorSpec::new(),
+            event: ColorSpec::new(),
+            exception: ColorSpec::new(),
+            frame: ColorSpec::new(),
+            own_frame: ColorSpec::new(),
+            header: ColorSpec::new(),
+            warning: ColorSpec::new(),
+            error: ColorSpec::new(),
+        }
+    }
+
+    /// Theme with bright, bold colors for every span kind and column.
+    pub fn high_contrast() -> Self {
+        Self {
+            client: bold(fg(Color::Cyan)),
+            server: bold(fg(Color::Green)),
+            producer: bold(fg(Color::Magenta)),
+            consumer: bold(fg(Color::Blue)),
+            internal: bold(fg(Color::White)),
+            duration: bold(ColorSpec::new()),
+            timing: fg(Color::Cyan),
+            event: fg(Color::White),
+            exception: bold(fg(Color::Red)),
+            frame: fg(Color::Red),
+            own_frame: bold(fg(Color::Yellow)),
+            header: bold(fg(Color::White)),
+            warning: bold(fg(Color::Yellow)),
+            error: bold(fg(Color::Red)),
+        }
+    }
+
+    /// Theme without colors, which uses bold and underlined text instead.
+    pub fn monochrome_bold() -> Self {
+        let mut underline = ColorSpec::new();
+        underline.set_underline(true);
+        Self {
+            client: bold(ColorSpec::new()),
+            server: bold(ColorSpec::new()),
+            producer: bold(ColorSpec::new()),
+            consumer: bold(ColorSpec::new()),
+            internal: bold(ColorSpec::new()),
+            exception: bold(ColorSpec::new()),
+            own_frame: underline.clone(),
+            header: bold(ColorSpec::new()),
+            warning: underline,
+            error: bold(ColorSpec::new()),
+            ..Self::unstyled()
+        }
+    }
+
+    pub(crate) fn kind(&self, kind: &SpanKind) -> &ColorSpec {
+        match kind {
+            SpanKind::Client => &self.client,
+            SpanKind::Server => &self.server,
+            SpanKind::Producer => &self.producer,
+            SpanKind::Consumer => &self.consumer,
+            SpanKind::Internal => &self.internal,
+        }
+    }
+}
+
+fn fg(color: Color) -> ColorSpec {
+    let mut spec = ColorSpec::new();
+    spec.set_fg(Some(color));
+    spec
+}
+
+fn bold(mut spec: ColorSpec) -> ColorSpec {
+    spec.set_bold(true);
+    spec
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(ColorMode::Always,     false, &[("NO_COLOR", "1")]       => ColorChoice::Always     ; "always ignores env")]
+    #[test_case(ColorMode::AlwaysAnsi, false, &[]                        => ColorChoice::AlwaysAnsi ; "always ansi")]
+    #[test_case(ColorMode::Never,      true,  &[("CLICOLOR_FORCE", "1")] => ColorChoice::Never      ; "never ignores env")]
+    #[test_case(ColorMode::Auto,       true,  &[]                        => ColorChoice::Auto       ; "auto terminal")]
+    #[test_case(ColorMode::Auto,       false, &[]                        => ColorChoice::Never      ; "auto no terminal")]
+    #[test_case(ColorMode::Auto,       true,  &[("TERM", "dumb")]        => ColorChoice::Never      ; "auto dumb terminal")]
+    #[test_case(ColorMode::Auto,       true,  &[("NO_COLOR", "1")]       => ColorChoice::Never      ; "auto no color")]
+    #[test_case(ColorMode::Auto,       true,  &[("NO_COLOR", "")]        => ColorChoice::Auto       ; "auto empty no color")]
+    #[test_case(ColorMode::Auto,       false, &[("CLICOLOR_FORCE", "1")] => ColorChoice::Always     ; "auto force")]
+    #[test_case(ColorMode::Auto,       false, &[("CLICOLOR_FORCE", "0")] => ColorChoice::Never      ; "auto force disabled")]
+    #[test_case(ColorMode::Auto,       false, &[("NO_COLOR", "1"), ("CLICOLOR_FORCE", "1")] => ColorChoice::Never ; "auto no color wins")]
+    fn resolve(mode: ColorMode, is_terminal: bool, env: &[(&str, &str)]) -> ColorChoice {
+        mode.resolve_with_env(is_terminal, |key| {
+            env.iter()
+                .find(|(k, _)| *k == key)
+                .map(|(_, v)| OsString::from(v))
+        })
+    }
+}
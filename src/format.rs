@@ -1,4 +1,6 @@
+use std::borrow::Cow;
 use std::time::{Duration, SystemTime};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 pub(crate) fn format_duration(d: Duration) -> String {
     let secs = d.as_secs();
@@ -15,6 +17,92 @@ pub(crate) fn format_duration(d: Duration) -> String {
     }
 }
 
+/// Width of the string in terminal columns.
+pub(crate) fn display_width(s: &str) -> usize {
+    s.width()
+}
+
+/// Cut the string to at most `width` terminal columns.
+pub(crate) fn clip(s: &str, width: usize) -> &str {
+    let mut used = 0;
+    for (i, c) in s.char_indices() {
+        used += c.width().unwrap_or(0);
+        if used > width {
+            return &s[..i];
+        }
+    }
+    s
+}
+
+/// Truncate the string to at most `width` terminal columns. Truncated strings end in an ellipsis
+/// to make it obvious that text was cut.
+pub(crate) fn truncate(s: &str, width: usize) -> Cow<'_, str> {
+    if display_width(s) <= width {
+        s.into()
+    } else if width == 0 {
+        "".into()
+    } else {
+        format!("{}…", clip(s, width - 1)).into()
+    }
+}
+
+/// Wrap the string into lines of at most `width` terminal columns, breaking at spaces if possible.
+/// The first line may have a different width.
+pub(crate) fn wrap(s: &str, first_width: usize, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = String::new();
+    let mut line_width = first_width;
+    for word in s.split(' ') {
+        if line.is_empty() && word.is_empty() && !lines.is_empty() {
+            // Don't start continuation lines with whitespace.
+            continue;
+        }
+
+        let candidate = if line.is_empty() {
+            word.to_owned()
+        } else {
+            format!("{} {}", line, word)
+        };
+        if display_width(&candidate) <= line_width {
+            line = candidate;
+            continue;
+        }
+
+        if !line.is_empty() {
+            lines.push(std::mem::take(&mut line));
+            line_width = width;
+        }
+
+        // Break words, which are too long to fit in a line on their own.
+        let mut word = word;
+        while display_width(word) > line_width {
+            let mut piece = clip(word, line_width);
+            if piece.is_empty() {
+                // Always make progress, even if the first character is too wide for the line.
+                let end = word.chars().next().map_or(0, char::len_utf8);
+                piece = &word[..end];
+            }
+            lines.push(piece.to_owned());
+            word = &word[piece.len()..];
+            line_width = width;
+        }
+        line = word.to_owned();
+    }
+    if !line.is_empty() || lines.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+/// Pad the string with spaces on the right to fill `width` terminal columns.
+pub(crate) fn pad(s: &str, width: usize) -> String {
+    format!(
+        "{s}{padding}",
+        s = s,
+        padding = " ".repeat(width.saturating_sub(display_width(s)))
+    )
+}
+
 pub(crate) fn format_timing(
     available_width: usize,
     parent_start: SystemTime,
@@ -32,7 +120,8 @@ pub(crate) fn format_timing(
 
     let scale = available_width as f64 / parent_duration.as_secs_f64();
     let start_gap = start.duration_since(parent_start).unwrap_or_default();
-    let fill_len = ((duration.as_secs_f64() * scale).round() as usize).max(1);
+    // A span may outlast its timing parent, so its bar is clamped to the available width.
+    let fill_len = ((duration.as_secs_f64() * scale).round() as usize).clamp(1, available_width);
     let start_len =
         ((start_gap.as_secs_f64() * scale).round() as usize).min(available_width - fill_len);
 
@@ -44,6 +133,40 @@ pub(crate) fn format_timing(
     )
 }
 
+/// Format the time as an RFC 3339 timestamp in UTC with millisecond precision, e.g.
+/// `2022-03-04T13:37:00.123Z`.
+pub(crate) fn format_timestamp(time: SystemTime) -> String {
+    let since_epoch = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let secs = since_epoch.as_secs();
+    let (days, secs_of_day) = (secs / 86400, secs % 86400);
+
+    // Convert days since the epoch to a civil date. See
+    // https://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        secs_of_day / 3600,
+        secs_of_day / 60 % 60,
+        secs_of_day % 60,
+        since_epoch.subsec_millis()
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -58,12 +181,61 @@ mod tests {
         assert_eq!(expected.to_string(), format_duration(d));
     }
 
+    #[test_case("abc",    5, "abc" ; "short")]
+    #[test_case("abc",    2, "ab"  ; "long")]
+    #[test_case("äöü",    2, "äö"  ; "multi byte")]
+    #[test_case("日本語", 3, "日"  ; "wide")]
+    #[test_case("abc",    0, ""    ; "zero width")]
+    fn clipped(s: &'static str, width: usize, expected: &'static str) {
+        assert_eq!(expected, clip(s, width));
+    }
+
+    #[test_case("abc",    3, "abc"  ; "fits")]
+    #[test_case("abcd",   3, "ab…"  ; "long")]
+    #[test_case("Grüße",  4, "Grü…" ; "multi byte")]
+    #[test_case("日本語", 5, "日本…" ; "wide")]
+    #[test_case("日本語", 4, "日…"   ; "wide with gap")]
+    #[test_case("abc",    1, "…"    ; "only ellipsis")]
+    #[test_case("abc",    0, ""     ; "zero width")]
+    fn truncated(s: &'static str, width: usize, expected: &'static str) {
+        assert_eq!(expected, truncate(s, width));
+    }
+
+    #[test_case("a b c",     5, 5, &["a b c"]               ; "fits")]
+    #[test_case("ab cd ef",  5, 5, &["ab cd", "ef"]         ; "breaks at spaces")]
+    #[test_case("ab cd ef",  2, 5, &["ab", "cd ef"]         ; "different first width")]
+    #[test_case("abcdefg h", 3, 3, &["abc", "def", "g h"]   ; "breaks long words")]
+    #[test_case("a  b",      4, 4, &["a  b"]                ; "keeps spaces")]
+    #[test_case("ab  cd",    3, 3, &["ab ", "cd"]           ; "skips leading spaces")]
+    #[test_case("",          3, 3, &[""]                    ; "empty")]
+    fn wrapped(s: &'static str, first_width: usize, width: usize, expected: &[&'static str]) {
+        assert_eq!(expected.to_vec(), wrap(s, first_width, width));
+    }
+
+    #[test_case(0,             0,   "1970-01-01T00:00:00.000Z" ; "epoch")]
+    #[test_case(951_782_400,   5,   "2000-02-29T00:00:00.005Z" ; "leap day")]
+    #[test_case(1_646_401_020, 123, "2022-03-04T13:37:00.123Z" ; "recent")]
+    #[test_case(4_107_542_399, 999, "2100-02-28T23:59:59.999Z" ; "no leap year")]
+    fn timestamp(secs: u64, millis: u64, expected: &str) {
+        let time =
+            SystemTime::UNIX_EPOCH + Duration::from_secs(secs) + Duration::from_millis(millis);
+        assert_eq!(expected, format_timestamp(time));
+    }
+
+    #[test_case("ab",   4, "ab  " ; "short")]
+    #[test_case("日本", 5, "日本 " ; "wide")]
+    #[test_case("abc",  2, "abc"  ; "too long")]
+    fn padded(s: &'static str, width: usize, expected: &'static str) {
+        assert_eq!(expected, pad(s, width));
+    }
+
     #[test_case(15, 10,  1, 2, '=', "  ===          " ; "basic case")]
     #[test_case( 0, 10,  1, 2, '=', ""                ; "zero available width")]
     #[test_case(15,  0,  1, 2, '=', "===============" ; "zero parent duration")]
     #[test_case(15, 10,  1, 0, '=', "  =            " ; "zero duration")]
     #[test_case(15, 10, -5, 1, '=', "==             " ; "starts before parent")]
     #[test_case(15, 10, 10, 1, '=', "             ==" ; "ends after parent")]
+    #[test_case(15, 10,  5, 20, '=', "===============" ; "longer than parent")]
     #[test_case(15, 10,  1, 2, 'a', "  aaa          " ; "different fill char")]
     fn timing(
         available_width: usize,
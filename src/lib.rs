@@ -4,16 +4,16 @@
 //! [opentelemetry rust]: https://github.com/open-telemetry/opentelemetry-rust
 //!
 //! ```text
-//! SE  my-awesome-books.com  GET /authors/:authorId/boo  500  584ms  ==================
+//! SE  my-awesome-books.com  GET /authors/:authorId/bo…  500  584ms  ==================
 //!  IN  middleware - expressInit                           0      0  =
 //!  IN  middleware - query                                 0      0  =
 //!  IN  middleware - session                               0  523ms  ================
 //!   CL  pg-pool.connect                                   0  303ms  =========
-//!   CL  sessions  SELECT sess FROM "session" WHERE sid    0  219ms           =======
+//!   CL  sessions  SELECT sess FROM "session" WHERE si…    0  219ms           =======
 //!  IN  middleware - initialize                            0      0                  =
 //!  IN  middleware - authenticate                          0      0                  =
 //!   user authenticated                                                              ·
-//!  IN  request handler - /authors/:authorId/books/:boo    0   59ms                  ==
+//!  IN  request handler - /authors/:authorId/books/:bo…    0   59ms                  ==
 //!   CL  book-service.book-service  POST /graphql        200   59ms                  ==
 //!    SE  book-service.book.service  POST /graphql       200      0                   =
 //!     IN  query                                           0      0                   =
@@ -40,24 +40,107 @@
 //! either the **async-std** or **tokio** feature for the `opentelemetry` crate. Otherwise spans
 //! will be exported synchronously.
 //!
+//! ## Output
+//!
+//! Traces are printed to stdout by default. Use `with_stderr` or `with_writer` on the pipeline
+//! builder to print them somewhere else, e.g. to keep stdout free for machine-readable output.
+//! `with_color_writer` accepts writers, which print colors themselves, like termcolor's
+//! `StandardStream`.
+//!
+//! Colors are used if the output is a terminal. This honors the `NO_COLOR` and `CLICOLOR_FORCE`
+//! environment variables and can be overridden with `with_color_mode`. Styles are set with
+//! `with_theme`. Themes use the `Color` and `ColorSpec` types of the [termcolor] crate, which this
+//! crate re-exports. termcolor is deliberately a public dependency. A new major version of
+//! termcolor will therefore be a breaking release of this crate.
+//!
+//! [termcolor]: https://docs.rs/termcolor
+//!
+//! Use `with_redaction` to keep sensitive data like tokens or email addresses out of the output.
+//!
+//! Use `with_trace_header` to separate traces with a header showing trace id and service and a
+//! footer showing span and error counts.
+//!
+//! Traces are printed once their root span ends. Use `with_max_trace_age` to print traces, whose
+//! root span takes too long or never ends, without it. `with_max_buffered_traces` and
+//! `with_max_buffered_spans_per_trace` limit the memory used by waiting spans. Use
+//! `with_grace_period` to include spans, which end after their root span, e.g. fire-and-forget
+//! tasks. Spans of incomplete traces are grouped under a placeholder for their missing parent. It
+//! spans the time of its children and shows the span id of the parent. With `install_simple`, spans
+//! called from another service are printed as roots, because their parent is known to be remote.
+//! Their row shows the span id of the remote parent. Exported spans don't tell whether their parent
+//! is remote, so `build_exporter` labels the missing parent of server and consumer spans as remote.
+//!
+//! `TracerProvider::force_flush`, e.g. using `global::force_flush_tracer_provider`, prints all
+//! buffered traces without shutting down. If you create the exporter with `build_exporter` to
+//! install it yourself, keep a handle from `StdoutTreeExporter::flush_handle` to do the same.
+//!
+//! Events only show their name by default. Use `with_event_attributes` to print their attributes
+//! after the name or on the following line. The status message of failed spans is printed below
+//! the span. Links are printed as the first children of a span and refer to the linked span by
+//! its kind, name and span id, if it's part of the same trace, or by trace and span id.
+//!
 //! # Attribute mapping
 //!
 //! The exporter makes use of [OpenTelemetry semantic conventions] to provide more useful output
 //! for known types of spans. Currently supported are:
 //!
-//! - HTTP: Shows method, host and path and uses status code to determine errors.
-//! - DB: Shows database name and statement or operation.
-//! - Exception events: shows exception type and message.
+//! - HTTP: Shows method, host and path and uses status code to determine warnings
+//!   (4xx) and errors (5xx).
+//! - DB: Shows database name and statement or operation and collection. Use `with_sql_options` to
+//!   print SQL statements on one line and mask their literals and comments.
+//! - RPC: Shows peer, service and method and uses the gRPC status code to determine errors.
+//! - Messaging: Shows destination, system, operation and message id or batch size.
+//! - Exception events: shows exception type and message and marks escaped exceptions. The stack
+//!   trace can be printed below the event using `with_stack_traces`.
+//!
+//! Spans following other conventions can be summarized by implementing `SpanSummarizer` and
+//! registering it with `with_summarizer` on the pipeline builder. For simple cases `with_template`
+//! fills a template like `"{cache.op} {cache.key}"` with the attributes of matching spans.
+//!
+//! Other spans show all their attributes sorted by key. Use `with_important_attributes` to show
+//! selected attributes first and `with_other_attributes_hidden` to hide the rest.
 //!
 //! [opentelemetry semantic conventions]: https://github.com/open-telemetry/opentelemetry-specification/tree/master/specification/trace/semantic_conventions
 #![doc(html_root_url = "https://docs.rs/opentelemetry-stdout-tree/0.1.0")]
 #![deny(missing_docs, unreachable_pub, missing_debug_implementations)]
 #![cfg_attr(test, deny(warnings))]
 
+mod buffer;
+mod color;
 mod format;
+mod matcher;
+mod output;
 mod print;
+mod redact;
 mod semantics;
+mod sql;
+mod stacktrace;
+mod template;
+#[cfg(test)]
+mod testing;
+mod tree;
+
+pub use crate::{
+    buffer::EvictionPolicy,
+    color::{ColorMode, Theme},
+    matcher::SpanMatcher,
+    print::EventAttributes,
+    redact::Redaction,
+    semantics::{SemanticInfo, SpanSummarizer},
+    sql::SqlOptions,
+    stacktrace::StackTraceOptions,
+    template::SpanTemplate,
+    tree::TreeStyle,
+};
+pub use regex::Regex;
+// termcolor is a public dependency on purpose, see the crate docs.
+pub use termcolor::{Color, ColorSpec};
 
+use crate::{
+    buffer::{BufferOptions, Trace, TraceBuffer},
+    output::{Output, SharedWriter},
+    print::PrintOptions,
+};
 use async_trait::async_trait;
 use opentelemetry::{
     global,
@@ -68,12 +151,16 @@ use opentelemetry::{
             ExportError,
         },
     },
-    trace::{SpanContext, SpanId, SpanKind, StatusCode, TraceId, TracerProvider},
+    trace::{Span as _, SpanId, TraceContextExt as _, TraceId, TraceResult, TracerProvider},
+    Context, Key,
 };
 use std::{
-    collections::{HashMap, HashSet},
-    time::SystemTime,
+    collections::HashSet,
+    io::Write,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
 };
+use termcolor::WriteColor;
 
 /// Create a new stdout tree exporter pipeline builder
 pub fn new_pipeline() -> StdoutTreePipelineBuilder {
@@ -83,25 +170,52 @@ pub fn new_pipeline() -> StdoutTreePipelineBuilder {
 /// Pipeline builder for stdout tree exporter
 #[derive(Debug)]
 pub struct StdoutTreePipelineBuilder {
-    timing_column_width: f64,
+    output: Output,
+    print_options: PrintOptions,
+    buffer_options: BufferOptions,
     trace_config: Option<sdk::trace::Config>,
 }
 
 impl Default for StdoutTreePipelineBuilder {
     fn default() -> Self {
         Self {
-            timing_column_width: 0.2,
+            output: Output::Stdout,
+            print_options: PrintOptions::default(),
+            buffer_options: BufferOptions::default(),
             trace_config: None,
         }
     }
 }
 
 impl StdoutTreePipelineBuilder {
+    /// Build the stdout tree span exporter without installing a pipeline
+    ///
+    /// The trace config is ignored. Use this to configure a tracer provider yourself, e.g. with a
+    /// batch span processor. Span processors don't forward `TracerProvider::force_flush` to their
+    /// exporter, so keep a [`FlushHandle`] to print buffered traces.
+    pub fn build_exporter(self) -> StdoutTreeExporter {
+        StdoutTreeExporter::new(self.output, self.print_options, self.buffer_options)
+    }
+
     /// Install an OpenTelemetry pipeline with the stdout tree span exporter
+    ///
+    /// Spans are exported synchronously when they end. `TracerProvider::force_flush`, e.g. using
+    /// [`global::force_flush_tracer_provider`], prints all buffered traces.
+    ///
+    /// The export runs on the thread ending the span, while holding a lock shared by all threads.
+    /// A slow writer delays the application and a panicking writer panics in application code.
+    /// Use [`build_exporter`](Self::build_exporter) with a batch span processor to print on a
+    /// background thread instead.
     pub fn install_simple(mut self) -> sdk::trace::Tracer {
-        let exporter = StdoutTreeExporter::new(self.timing_column_width);
+        let exporter =
+            StdoutTreeExporter::new(self.output, self.print_options, self.buffer_options);
+        lock(&exporter.state).remote_parents_known = true;
+        let processor = SpanProcessor {
+            state: exporter.state,
+            remote_parents: Mutex::new(HashSet::new()),
+        };
         let mut provider_builder =
-            sdk::trace::TracerProvider::builder().with_simple_exporter(exporter);
+            sdk::trace::TracerProvider::builder().with_span_processor(processor);
         if let Some(config) = self.trace_config.take() {
             provider_builder = provider_builder.with_config(config);
         }
@@ -119,7 +233,252 @@ impl StdoutTreePipelineBuilder {
     ///
     /// Value should be between 0 and 1. Default is 0.2;
     pub fn with_timing_column_width(mut self, timing_column_width: f64) -> Self {
-        self.timing_column_width = timing_column_width;
+        self.print_options.timing_column_width = timing_column_width;
+        self
+    }
+
+    /// Set how the nesting of spans and events is displayed
+    ///
+    /// Default is [`TreeStyle::Spaces`].
+    pub fn with_tree_style(mut self, tree_style: TreeStyle) -> Self {
+        self.print_options.tree_style = tree_style;
+        self
+    }
+
+    /// Set width of one indentation level in characters
+    ///
+    /// Default is 1 for [`TreeStyle::Spaces`] and 3 for tree guides.
+    pub fn with_indent_width(mut self, indent_width: usize) -> Self {
+        self.print_options.indent_width = Some(indent_width);
+        self
+    }
+
+    /// Wrap span details onto continuation lines instead of truncating them
+    ///
+    /// Status, duration and timing are only printed on the first line of a span.
+    pub fn with_wrapped_details(mut self, wrap_details: bool) -> Self {
+        self.print_options.wrap_details = wrap_details;
+        self
+    }
+
+    /// Print a header and footer around every trace
+    ///
+    /// The header shows the trace id, the service name and version of the root span and the start
+    /// time. The footer shows the number of spans and errors and the total duration.
+    pub fn with_trace_header(mut self, trace_header: bool) -> Self {
+        self.print_options.trace_header = trace_header;
+        self
+    }
+
+    /// Print traces incomplete, if their root span didn't arrive within this time
+    ///
+    /// Spans are buffered until the root span of their trace is exported. If the root span never
+    /// ends, e.g. in a long running worker loop or a crashed request handler, the trace is printed
+    /// without it once the first buffered span is older than `max_trace_age`. Missing parents are
+    /// shown as placeholder rows covering the time of their children and showing the parent's span
+    /// id. The age is checked whenever spans are exported. By default traces are buffered until
+    /// shutdown.
+    pub fn with_max_trace_age(mut self, max_trace_age: Duration) -> Self {
+        self.buffer_options.max_trace_age = Some(max_trace_age);
+        self
+    }
+
+    /// Limit the number of traces waiting for their root span
+    ///
+    /// When the limit is reached, the oldest trace is evicted according to the eviction policy.
+    /// A limit of 0 is treated as 1, because the trace of the exported span is always buffered.
+    /// By default the number of traces is not limited.
+    pub fn with_max_buffered_traces(mut self, max_traces: usize) -> Self {
+        self.buffer_options.max_traces = Some(max_traces);
+        self
+    }
+
+    /// Limit the number of spans buffered per trace
+    ///
+    /// When a trace reaches the limit, it's evicted according to the eviction policy and further
+    /// spans are buffered as if the trace was new. A limit of 0 is treated as 1, because the
+    /// exported span is always buffered. By default the number of spans is not limited.
+    pub fn with_max_buffered_spans_per_trace(mut self, max_spans: usize) -> Self {
+        self.buffer_options.max_spans_per_trace = Some(max_spans);
+        self
+    }
+
+    /// Set what happens to traces evicted from a full buffer
+    pub fn with_eviction_policy(mut self, eviction_policy: EvictionPolicy) -> Self {
+        self.buffer_options.eviction_policy = eviction_policy;
+        self
+    }
+
+    /// Wait this long after the root span of a trace ended before printing the trace
+    ///
+    /// Spans ending after their root, e.g. fire-and-forget tasks, and further local roots of the
+    /// same trace, e.g. a second request with the same remote parent, are printed as part of the
+    /// trace if they arrive within the grace period. Spans arriving even later are printed as
+    /// "late spans" below a marker, if their parent was printed within another grace period. To
+    /// recognize them, the span ids of printed traces are kept, limited like buffered traces by
+    /// [`StdoutTreePipelineBuilder::with_max_buffered_traces`] and
+    /// [`StdoutTreePipelineBuilder::with_max_buffered_spans_per_trace`].
+    ///
+    /// There's no timer. The grace period is checked whenever spans are exported and traces are
+    /// printed when the tracer provider is flushed or shut down. A trace, whose grace period ended,
+    /// stays buffered until then. By default traces are printed as soon as their root span ends.
+    pub fn with_grace_period(mut self, grace_period: Duration) -> Self {
+        self.buffer_options.grace_period = Some(grace_period);
+        self
+    }
+
+    /// Set whether and where attributes of events are printed
+    pub fn with_event_attributes(mut self, event_attributes: EventAttributes) -> Self {
+        self.print_options.event_attributes = event_attributes;
+        self
+    }
+
+    /// Print stack traces of exception events below the event
+    ///
+    /// Stack traces are taken from the `exception.stacktrace` attribute. By default they're not
+    /// printed.
+    pub fn with_stack_traces(mut self, options: StackTraceOptions) -> Self {
+        self.print_options.stack_traces = Some(options);
+        self
+    }
+
+    /// Add a summarizer, which is tried before the built-in ones
+    ///
+    /// Summarizers are tried in the order they were added. The first one to return a summary
+    /// wins.
+    pub fn with_summarizer<S: SpanSummarizer + 'static>(mut self, summarizer: S) -> Self {
+        self.print_options
+            .summarizers
+            .before
+            .push(Arc::new(summarizer));
+        self
+    }
+
+    /// Add a template, which summarizes matching spans
+    ///
+    /// Templates are tried before the built-in summarizers, together with the summarizers added
+    /// by [`StdoutTreePipelineBuilder::with_summarizer`], in the order they were added.
+    pub fn with_template(self, template: SpanTemplate) -> Self {
+        self.with_summarizer(template)
+    }
+
+    /// Add a summarizer, which is tried after the built-in ones
+    ///
+    /// Summarizers are tried in the order they were added. The first one to return a summary
+    /// wins.
+    pub fn with_fallback_summarizer<S: SpanSummarizer + 'static>(mut self, summarizer: S) -> Self {
+        self.print_options
+            .summarizers
+            .after
+            .push(Arc::new(summarizer));
+        self
+    }
+
+    /// Set how SQL statements of database spans are compacted
+    ///
+    /// By default statements are printed verbatim. Use [`SqlOptions::compact`] to print them on a
+    /// single line without select lists and literals.
+    pub fn with_sql_options(mut self, sql_options: SqlOptions) -> Self {
+        self.print_options.summarizers.sql = sql_options;
+        self
+    }
+
+    /// Set rules to remove sensitive data from printed traces
+    pub fn with_redaction(mut self, redaction: Redaction) -> Self {
+        self.print_options.redaction = redaction;
+        self
+    }
+
+    /// Show these attributes first, in the given order, for spans matching the matcher
+    ///
+    /// Only applies to spans without known semantic conventions, which show all their attributes.
+    /// Other attributes are shown sorted by key after the important ones. If multiple matchers
+    /// match a span, their attributes are shown in the order they were added.
+    pub fn with_important_attributes<I, K>(mut self, matcher: SpanMatcher, keys: I) -> Self
+    where
+        I: IntoIterator<Item = K>,
+        K: Into<Key>,
+    {
+        self.print_options
+            .summarizers
+            .attributes
+            .important
+            .push((matcher, keys.into_iter().map(Into::into).collect()));
+        self
+    }
+
+    /// Hide attributes, which were not configured as important
+    ///
+    /// See [`StdoutTreePipelineBuilder::with_important_attributes`].
+    pub fn with_other_attributes_hidden(mut self, hide_others: bool) -> Self {
+        self.print_options.summarizers.attributes.hide_others = hide_others;
+        self
+    }
+
+    /// Set width of the terminal in characters
+    ///
+    /// By default the width is detected from the terminal connected to the output. If that's not
+    /// possible, e.g. when printing to a file, a width of 80 characters is used.
+    pub fn with_terminal_width(mut self, terminal_width: usize) -> Self {
+        self.print_options.terminal_width = Some(terminal_width);
+        self
+    }
+
+    /// Set whether the output contains colors
+    ///
+    /// Default is [`ColorMode::Auto`], which honors the `NO_COLOR` and `CLICOLOR_FORCE`
+    /// environment variables.
+    pub fn with_color_mode(mut self, color_mode: ColorMode) -> Self {
+        self.print_options.color_mode = color_mode;
+        self
+    }
+
+    /// Set the styles used to print traces
+    ///
+    /// Default is [`Theme::default`]. See [`Theme`] for other built-in themes.
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.print_options.theme = theme;
+        self
+    }
+
+    /// Print traces to stdout (default)
+    pub fn with_stdout(mut self) -> Self {
+        self.output = Output::Stdout;
+        self
+    }
+
+    /// Print traces to stderr
+    pub fn with_stderr(mut self) -> Self {
+        self.output = Output::Stderr;
+        self
+    }
+
+    /// Print traces to the given writer, e.g. a file or a `Vec<u8>`
+    ///
+    /// Each trace is written with a single call to [`Write::write_all`].
+    pub fn with_writer<W: Write + Send + 'static>(mut self, writer: W) -> Self {
+        self.output = Output::Writer(Box::new(writer));
+        self
+    }
+
+    /// Print traces to the given writer, which supports colors, e.g. a
+    /// [`termcolor::StandardStream`]
+    ///
+    /// The writer decides how colors are printed, e.g. using the Windows console API, and whether
+    /// they're printed at all. Traces are rendered directly into the writer and it's flushed after
+    /// every trace. [`ColorMode::Never`] and the `NO_COLOR` environment variable still disable
+    /// colors.
+    pub fn with_color_writer<W: WriteColor + Send + 'static>(mut self, writer: W) -> Self {
+        self.output = Output::ColorWriter(Box::new(writer));
+        self
+    }
+
+    /// Print traces to the given writer, which is shared behind a mutex
+    ///
+    /// The mutex is locked while a trace is written, which allows reading the output from another
+    /// place, e.g. in tests.
+    pub fn with_shared_writer<W: Write + Send + 'static>(mut self, writer: Arc<Mutex<W>>) -> Self {
+        self.output = Output::Writer(Box::new(SharedWriter(writer)));
         self
     }
 
@@ -131,100 +490,217 @@ impl StdoutTreePipelineBuilder {
 }
 
 /// Stdout tree span exporter
+///
+/// Spans are buffered until their trace is complete. Use [`StdoutTreeExporter::flush_handle`] to
+/// print buffered traces on demand.
 #[derive(Debug)]
 pub struct StdoutTreeExporter {
-    buffer: HashMap<TraceId, HashMap<SpanId, Vec<SpanData>>>,
-    timing_column_width: f64,
+    state: Arc<Mutex<ExporterState>>,
 }
 
 impl StdoutTreeExporter {
-    fn new(timing_column_width: f64) -> Self {
+    fn new(output: Output, print_options: PrintOptions, buffer_options: BufferOptions) -> Self {
         Self {
-            buffer: HashMap::new(),
-            timing_column_width,
+            state: Arc::new(Mutex::new(ExporterState {
+                buffer: TraceBuffer::new(buffer_options),
+                output,
+                print_options,
+                remote_parents_known: false,
+            })),
+        }
+    }
+
+    /// Print all buffered traces, including incomplete ones, and keep the exporter usable
+    ///
+    /// Spans, whose parent wasn't exported yet, are grouped under placeholder spans.
+    pub fn force_flush(&self) -> ExportResult {
+        lock(&self.state).flush()
+    }
+
+    /// Get a handle, which can flush the exporter after it was installed in a tracer provider
+    pub fn flush_handle(&self) -> FlushHandle {
+        FlushHandle {
+            state: self.state.clone(),
         }
     }
 }
 
-#[async_trait]
-impl SpanExporter for StdoutTreeExporter {
-    async fn export(&mut self, batch: Vec<SpanData>) -> ExportResult {
-        for span_data in batch {
-            if span_data.parent_span_id == SpanId::INVALID || span_data.span_context.is_remote() {
-                // TODO: This assumes that a trace only has 1 root span, which can be identified by
-                // a zero-ed parent span id or by having a remote parent. Is this true?
-                let mut trace = self
-                    .buffer
-                    .remove(&span_data.span_context.trace_id())
-                    .unwrap_or_default();
-                trace.insert(SpanId::INVALID, vec![span_data]);
-                print::print_trace(trace, self.timing_column_width).map_err(Error::IoError)?;
-            } else {
-                self.buffer
-                    .entry(span_data.span_context.trace_id())
-                    .or_default()
-                    .entry(span_data.parent_span_id)
-                    .or_default()
-                    .push(span_data);
+/// Handle to flush a [`StdoutTreeExporter`], which was moved into a tracer provider
+///
+/// Only needed for exporters created with [`StdoutTreePipelineBuilder::build_exporter`]. The
+/// span processors of OpenTelemetry don't forward `TracerProvider::force_flush` to exporters, so
+/// buffered traces need to be flushed using this handle. Spans, which ended but weren't exported
+/// yet, are not included. With the batch span processor, call `TracerProvider::force_flush` first
+/// to export them.
+///
+/// ```
+/// use opentelemetry::sdk::trace::TracerProvider;
+///
+/// let exporter = opentelemetry_stdout_tree::new_pipeline().build_exporter();
+/// let flush_handle = exporter.flush_handle();
+/// let provider = TracerProvider::builder()
+///     .with_simple_exporter(exporter)
+///     .build();
+/// // Create spans...
+/// flush_handle.force_flush().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct FlushHandle {
+    state: Arc<Mutex<ExporterState>>,
+}
+
+impl FlushHandle {
+    /// Print all buffered traces, including incomplete ones
+    ///
+    /// See [`StdoutTreeExporter::force_flush`].
+    pub fn force_flush(&self) -> ExportResult {
+        lock(&self.state).flush()
+    }
+}
+
+/// Lock the state. A panic while printing leaves the buffer in a consistent state, so the state is
+/// used even if the mutex was poisoned.
+fn lock(state: &Mutex<ExporterState>) -> MutexGuard<'_, ExporterState> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+#[derive(Debug)]
+struct ExporterState {
+    buffer: TraceBuffer,
+    output: Output,
+    print_options: PrintOptions,
+    /// Whether spans with a remote parent are marked when they're exported. Only the span
+    /// processor knows the parent context of a span.
+    remote_parents_known: bool,
+}
+
+impl ExporterState {
+    /// Export spans together with whether their parent is remote.
+    fn export(&mut self, batch: impl IntoIterator<Item = (SpanData, bool)>) -> ExportResult {
+        let now = Instant::now();
+        for (span_data, has_remote_parent) in batch {
+            if self.buffer.is_late(&span_data) {
+                let trace_id = span_data.span_context.trace_id();
+                let trace = self.buffer.take_late(span_data, now);
+                self.print_dropped()?;
+                print::print_late_spans(trace_id, trace, &mut self.output, &self.print_options)
+                    .map_err(Error::IoError)?;
+                continue;
+            }
+
+            for (trace_id, trace) in self.buffer.push(span_data, has_remote_parent, now) {
+                self.print_trace(trace_id, trace)?;
+            }
+            // Without a grace period, the trace of a root span is ready immediately.
+            for (trace_id, trace) in self.buffer.take_ready(now) {
+                self.print_trace(trace_id, trace)?;
             }
         }
 
+        for (trace_id, trace) in self.buffer.take_expired(now) {
+            self.print_trace(trace_id, trace)?;
+        }
+
         Ok(())
     }
 
-    fn shutdown(&mut self) {
-        let trace_ids: Vec<_> = self.buffer.keys().cloned().collect();
-        for trace_id in trace_ids {
-            let mut trace = self.buffer.remove(&trace_id).expect("key must exist");
-            let span_ids: HashSet<_> = trace
-                .values()
-                .flatten()
-                .map(|span_data| span_data.span_context.span_id())
-                .collect();
-            let parent_span_ids = trace.keys().cloned().filter(|x| !span_ids.contains(x));
-            trace.insert(
-                SpanId::INVALID,
-                parent_span_ids
-                    .map(|parent_span_id| SpanData {
-                        span_context: SpanContext::new(
-                            trace_id,
-                            parent_span_id,
-                            /* trace_flags */ Default::default(),
-                            /* is_remote */ false,
-                            /* trace_trace */ Default::default(),
-                        ),
-                        parent_span_id: SpanId::INVALID,
-                        span_kind: SpanKind::Internal,
-                        name: "ORPHANED".into(),
-                        start_time: SystemTime::now(),
-                        end_time: SystemTime::now(),
-                        attributes: sdk::trace::EvictedHashMap::new(0, 0),
-                        events: sdk::trace::EvictedQueue::new(0),
-                        links: sdk::trace::EvictedQueue::new(0),
-                        status_code: StatusCode::Unset,
-                        status_message: "".into(),
-                        resource: None,
-                        instrumentation_lib: sdk::InstrumentationLibrary::new(
-                            "opentelemetry-stdout-tree",
-                            None,
-                        ),
-                    })
-                    .collect(),
-            );
-
-            // We're in shutdown. So we're doing a best effort attempt to print traces and silently
-            // ignore any errors.
-            let _ = print::print_trace(trace, self.timing_column_width);
+    /// Print all buffered traces and the number of dropped traces. Stops at the first error and
+    /// the remaining traces are discarded, like the traces of a failed export.
+    fn flush(&mut self) -> ExportResult {
+        for (trace_id, trace) in self.buffer.take_all(Instant::now()) {
+            self.print_trace(trace_id, trace)?;
+        }
+        self.print_dropped()
+    }
+
+    /// Print a buffered trace. Traces dropped since the last printed trace are reported first, so
+    /// that long running processes see them before shutdown.
+    fn print_trace(&mut self, trace_id: TraceId, trace: Trace) -> ExportResult {
+        self.print_dropped()?;
+        print::print_buffered_trace(
+            trace_id,
+            trace,
+            self.remote_parents_known,
+            &mut self.output,
+            &self.print_options,
+        )
+        .map_err(Error::IoError)?;
+        Ok(())
+    }
+
+    fn print_dropped(&mut self) -> ExportResult {
+        print::print_dropped(
+            self.buffer.take_dropped(),
+            &mut self.output,
+            &self.print_options,
+        )
+        .map_err(Error::IoError)?;
+        Ok(())
+    }
+}
+
+/// Span processor installed by [`StdoutTreePipelineBuilder::install_simple`]. Unlike the simple
+/// span processor of the SDK, it forwards `force_flush` to the exporter and records which spans
+/// have a remote parent, so that they're printed as roots.
+#[derive(Debug)]
+struct SpanProcessor {
+    state: Arc<Mutex<ExporterState>>,
+    /// Spans with a remote parent, which haven't ended yet.
+    remote_parents: Mutex<HashSet<SpanId>>,
+}
+
+impl sdk::trace::SpanProcessor for SpanProcessor {
+    fn on_start(&self, span: &mut sdk::trace::Span, cx: &Context) {
+        if cx.has_active_span() && cx.span().span_context().is_remote() {
+            let span_id = span.span_context().span_id();
+            self.remote_parents
+                .lock()
+                .unwrap_or_else(PoisonError::into_inner)
+                .insert(span_id);
         }
     }
+
+    fn on_end(&self, span: SpanData) {
+        let has_remote_parent = self
+            .remote_parents
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(&span.span_context.span_id());
+        if let Err(err) = lock(&self.state).export([(span, has_remote_parent)]) {
+            global::handle_error(err);
+        }
+    }
+
+    fn force_flush(&self) -> TraceResult<()> {
+        lock(&self.state).flush()
+    }
+
+    fn shutdown(&mut self) -> TraceResult<()> {
+        lock(&self.state).flush()
+    }
+}
+
+#[async_trait]
+impl SpanExporter for StdoutTreeExporter {
+    async fn export(&mut self, batch: Vec<SpanData>) -> ExportResult {
+        // Without the parent context, spans with a remote parent can't be told apart from spans
+        // with a local parent, which wasn't exported yet.
+        lock(&self.state).export(batch.into_iter().map(|span_data| (span_data, false)))
+    }
+
+    fn shutdown(&mut self) {
+        // We're in shutdown. So we're doing a best effort attempt to print traces and silently
+        // ignore any errors.
+        let _ = lock(&self.state).flush();
+    }
 }
 
 /// Errors that occurred during span export.
 #[derive(thiserror::Error, Debug)]
 #[non_exhaustive]
 pub enum Error {
-    /// Printing to stdout failed.
-    #[error("write to stdout failed with {0}")]
+    /// Writing to the output failed.
+    #[error("write to output failed with {0}")]
     IoError(std::io::Error),
 }
 
@@ -233,3 +709,169 @@ impl ExportError for Error {
         "stdout-tree"
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{span, span_id, trace_id};
+    use opentelemetry::trace::{SpanContext, SpanKind};
+    use pretty_assertions::assert_eq;
+    use std::time::SystemTime;
+
+    #[test]
+    fn dropped_before_next_trace() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut exporter = new_pipeline()
+            .with_shared_writer(output.clone())
+            .with_terminal_width(60)
+            .with_timing_column_width(0.0)
+            .with_max_buffered_traces(1)
+            .with_eviction_policy(EvictionPolicy::Drop)
+            .build_exporter();
+        let mut other_trace = span(3, 2, "other", vec![]);
+        other_trace.span_context = SpanContext::new(
+            trace_id(2),
+            span_id(3),
+            Default::default(),
+            false,
+            Default::default(),
+        );
+
+        let batch = vec![other_trace, span(2, 1, "child", vec![])];
+        async_std::task::block_on(exporter.export(batch)).unwrap();
+        assert!(output.lock().unwrap().is_empty());
+
+        async_std::task::block_on(exporter.export(vec![span(1, 0, "root", vec![])])).unwrap();
+        let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            vec![
+                "dropped 1 incomplete trace (1 span), buffer full",
+                "SE  root                                            0   10ms",
+                " SE  child                                          0   10ms",
+            ],
+            output.lines().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn force_flush() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut exporter = new_pipeline()
+            .with_shared_writer(output.clone())
+            .with_terminal_width(40)
+            .with_timing_column_width(0.0)
+            .build_exporter();
+        let flush_handle = exporter.flush_handle();
+        let take_output =
+            || String::from_utf8(std::mem::take(&mut *output.lock().unwrap())).unwrap();
+
+        async_std::task::block_on(exporter.export(vec![span(2, 1, "child", vec![])])).unwrap();
+        assert_eq!("", take_output());
+
+        flush_handle.force_flush().unwrap();
+        assert_eq!(
+            vec![
+                "incomplete trace: the root span has not…",
+                "IN  0000000000000001  remot…    0   10ms",
+                " SE  child                      0   10ms",
+            ],
+            take_output().lines().collect::<Vec<_>>()
+        );
+
+        // The exporter is still usable after flushing.
+        async_std::task::block_on(exporter.export(vec![span(1, 0, "root", vec![])])).unwrap();
+        assert_eq!("SE  root                        0   10ms\n", take_output());
+    }
+
+    #[test]
+    fn child_outlives_root() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut exporter = new_pipeline()
+            .with_shared_writer(output.clone())
+            .with_terminal_width(40)
+            .with_grace_period(Duration::from_secs(60))
+            .build_exporter();
+        let flush_handle = exporter.flush_handle();
+        let mut child = span(2, 1, "child", vec![]);
+        child.start_time = SystemTime::UNIX_EPOCH + Duration::from_millis(5);
+        child.end_time = SystemTime::UNIX_EPOCH + Duration::from_millis(500);
+
+        let batch = vec![span(1, 0, "root", vec![]), child];
+        async_std::task::block_on(exporter.export(batch)).unwrap();
+        flush_handle.force_flush().unwrap();
+        assert_eq!(
+            vec![
+                "SE  root                0   10ms  =     ",
+                " SE  child              0  495ms  ======",
+            ],
+            String::from_utf8(output.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn flush_error() {
+        struct FailingWriter;
+
+        impl Write for FailingWriter {
+            fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::ErrorKind::BrokenPipe.into())
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let mut exporter = new_pipeline().with_writer(FailingWriter).build_exporter();
+        let mut other_trace = span(3, 2, "other", vec![]);
+        other_trace.span_context = SpanContext::new(
+            trace_id(2),
+            span_id(3),
+            Default::default(),
+            false,
+            Default::default(),
+        );
+        let batch = vec![other_trace, span(2, 1, "child", vec![])];
+        async_std::task::block_on(exporter.export(batch)).unwrap();
+
+        assert!(exporter.force_flush().is_err());
+        assert!(lock(&exporter.state)
+            .buffer
+            .take_all(Instant::now())
+            .is_empty());
+    }
+
+    #[test]
+    fn remote_parent_guessed() {
+        let output = Arc::new(Mutex::new(Vec::new()));
+        let mut exporter = new_pipeline()
+            .with_shared_writer(output.clone())
+            .with_terminal_width(60)
+            .with_timing_column_width(0.0)
+            .build_exporter();
+        let mut internal = span(4, 3, "internal", vec![]);
+        internal.span_kind = SpanKind::Internal;
+        internal.start_time += Duration::from_millis(20);
+        internal.end_time += Duration::from_millis(20);
+
+        let batch = vec![span(2, 1, "server", vec![]), internal];
+        async_std::task::block_on(exporter.export(batch)).unwrap();
+        exporter.force_flush().unwrap();
+        assert_eq!(
+            vec![
+                "incomplete trace: the root span has not been exported yet",
+                "IN  0000000000000001  remote parent                 0   10ms",
+                " SE  server                                         0   10ms",
+                "IN  0000000000000003  parent missing                0   10ms",
+                " IN  internal                                       0   10ms",
+            ],
+            String::from_utf8(output.lock().unwrap().clone())
+                .unwrap()
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+}
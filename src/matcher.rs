@@ -0,0 +1,150 @@
+use opentelemetry::{sdk::export::trace::SpanData, trace::SpanKind, Key};
+
+/// Selects spans, e.g. to configure which attributes are important for them.
+///
+/// All conditions must match. A matcher without conditions matches every span.
+///
+/// ```
+/// use opentelemetry::trace::SpanKind;
+/// use opentelemetry_stdout_tree::SpanMatcher;
+///
+/// let matcher = SpanMatcher::new()
+///     .name("cache.*")
+///     .instrumentation_library("my-cache")
+///     .kind(SpanKind::Client)
+///     .has_attribute("cache.key");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct SpanMatcher {
+    name: Option<String>,
+    instrumentation_library: Option<String>,
+    kind: Option<SpanKind>,
+    attributes: Vec<Key>,
+}
+
+impl SpanMatcher {
+    /// Create a matcher, which matches every span.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match spans with a name matching the glob pattern. `*` matches any number of characters
+    /// and `?` matches exactly one character.
+    pub fn name(mut self, pattern: impl Into<String>) -> Self {
+        self.name = Some(pattern.into());
+        self
+    }
+
+    /// Match spans created by the instrumentation library with this name.
+    pub fn instrumentation_library(mut self, name: impl Into<String>) -> Self {
+        self.instrumentation_library = Some(name.into());
+        self
+    }
+
+    /// Match spans of this kind.
+    pub fn kind(mut self, kind: SpanKind) -> Self {
+        self.kind = Some(kind);
+        self
+    }
+
+    /// Match spans, which have an attribute with this key. Can be called multiple times to
+    /// require several attributes.
+    pub fn has_attribute(mut self, key: impl Into<Key>) -> Self {
+        self.attributes.push(key.into());
+        self
+    }
+
+    pub(crate) fn matches(&self, span_data: &SpanData) -> bool {
+        self.name
+            .as_ref()
+            .is_none_or(|pattern| glob_match(pattern, &span_data.name))
+            && self
+                .instrumentation_library
+                .as_ref()
+                .is_none_or(|name| *name == span_data.instrumentation_lib.name)
+            && self
+                .kind
+                .as_ref()
+                .is_none_or(|kind| *kind == span_data.span_kind)
+            && self
+                .attributes
+                .iter()
+                .all(|key| span_data.attributes.get(key).is_some())
+    }
+}
+
+/// Match text against a glob pattern supporting `*` and `?`.
+fn glob_match(pattern: &str, text: &str) -> bool {
+    let pattern = pattern.chars().collect::<Vec<_>>();
+    let text = text.chars().collect::<Vec<_>>();
+    let (mut p, mut t) = (0, 0);
+    // Position after the last `*` in the pattern and the text position it was tried at, to
+    // backtrack if the rest of the pattern doesn't match.
+    let mut backtrack = None;
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                p += 1;
+                backtrack = Some((p, t));
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            _ => match backtrack {
+                Some((star_p, star_t)) => {
+                    p = star_p;
+                    t = star_t + 1;
+                    backtrack = Some((star_p, star_t + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+    use test_case::test_case;
+
+    #[test_case("cache.get", "cache.get" => true  ; "exact")]
+    #[test_case("cache.*",   "cache.get" => true  ; "star")]
+    #[test_case("cache.*",   "cache."    => true  ; "empty star")]
+    #[test_case("*.get",     "cache.get" => true  ; "leading star")]
+    #[test_case("c*e*t",     "cache.get" => true  ; "backtracking")]
+    #[test_case("cache.g?t", "cache.get" => true  ; "question mark")]
+    #[test_case("cache.?",   "cache."    => false ; "question mark needs character")]
+    #[test_case("cache.*",   "db.get"    => false ; "no match")]
+    #[test_case("cache",     "cache.get" => false ; "prefix only")]
+    fn glob(pattern: &str, text: &str) -> bool {
+        glob_match(pattern, text)
+    }
+
+    #[test]
+    fn conditions() {
+        let span_data = span(1, 0, "cache.get", vec![Key::new("cache.key").string("a")]);
+        assert!(SpanMatcher::new().matches(&span_data));
+        assert!(SpanMatcher::new()
+            .name("cache.*")
+            .instrumentation_library("test")
+            .matches(&span_data));
+        assert!(!SpanMatcher::new()
+            .name("cache.*")
+            .instrumentation_library("other")
+            .matches(&span_data));
+        assert!(SpanMatcher::new()
+            .kind(SpanKind::Server)
+            .has_attribute("cache.key")
+            .matches(&span_data));
+        assert!(!SpanMatcher::new()
+            .kind(SpanKind::Client)
+            .matches(&span_data));
+        assert!(!SpanMatcher::new()
+            .has_attribute("cache.key")
+            .has_attribute("cache.system")
+            .matches(&span_data));
+    }
+}
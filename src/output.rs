@@ -0,0 +1,166 @@
+use crate::color::ColorMode;
+use std::fmt;
+use std::io::{self, IsTerminal, Write};
+use std::sync::{Arc, Mutex};
+use termcolor::{Buffer, BufferWriter, ColorChoice, WriteColor};
+use terminal_size::Width;
+
+/// Terminal width used when the output is not connected to a terminal.
+const DEFAULT_TERMINAL_WIDTH: usize = 80;
+
+/// Destination of printed traces.
+pub(crate) enum Output {
+    Stdout,
+    Stderr,
+    Writer(Box<dyn Write + Send>),
+    ColorWriter(Box<dyn WriteColor + Send>),
+}
+
+impl Output {
+    /// Width of the terminal the output is connected to. Falls back to 80 characters for
+    /// arbitrary writers or if the width can't be determined.
+    pub(crate) fn terminal_width(&self) -> usize {
+        let size = match self {
+            Output::Stdout => terminal_size::terminal_size(),
+            Output::Stderr => stderr_terminal_size(),
+            Output::Writer(_) | Output::ColorWriter(_) => None,
+        };
+        size.map_or(DEFAULT_TERMINAL_WIDTH, |(Width(w), _)| w as usize)
+    }
+
+    fn is_terminal(&self) -> bool {
+        match self {
+            Output::Stdout => io::stdout().is_terminal(),
+            Output::Stderr => io::stderr().is_terminal(),
+            Output::Writer(_) => false,
+            // The writer decides itself whether it supports colors.
+            Output::ColorWriter(_) => true,
+        }
+    }
+
+    /// Render into a buffer using the given function and write the result to the output in one
+    /// go, so that traces printed from multiple threads don't interleave.
+    pub(crate) fn print(
+        &mut self,
+        color_mode: ColorMode,
+        render: impl FnOnce(&mut dyn WriteColor) -> io::Result<()>,
+    ) -> io::Result<()> {
+        let color_choice = color_mode.resolve(self.is_terminal());
+        match self {
+            Output::Stdout => print_buffered(BufferWriter::stdout(color_choice), render),
+            Output::Stderr => print_buffered(BufferWriter::stderr(color_choice), render),
+            Output::Writer(writer) => {
+                let mut buffer = match color_choice {
+                    ColorChoice::Always | ColorChoice::AlwaysAnsi => Buffer::ansi(),
+                    ColorChoice::Auto | ColorChoice::Never => Buffer::no_color(),
+                };
+                render(&mut buffer)?;
+                writer.write_all(buffer.as_slice())?;
+                writer.flush()
+            }
+            Output::ColorWriter(writer) if color_choice == ColorChoice::Never => {
+                let mut buffer = Buffer::no_color();
+                render(&mut buffer)?;
+                writer.write_all(buffer.as_slice())?;
+                writer.flush()
+            }
+            // Render directly into the writer, so that it can use its own way of coloring text,
+            // e.g. the Windows console API.
+            Output::ColorWriter(writer) => {
+                render(writer.as_mut())?;
+                writer.flush()
+            }
+        }
+    }
+}
+
+impl fmt::Debug for Output {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Output::Stdout => f.write_str("Stdout"),
+            Output::Stderr => f.write_str("Stderr"),
+            Output::Writer(_) => f.write_str("Writer"),
+            Output::ColorWriter(_) => f.write_str("ColorWriter"),
+        }
+    }
+}
+
+fn print_buffered(
+    bufwtr: BufferWriter,
+    render: impl FnOnce(&mut dyn WriteColor) -> io::Result<()>,
+) -> io::Result<()> {
+    let mut buffer = bufwtr.buffer();
+    render(&mut buffer)?;
+    bufwtr.print(&buffer)
+}
+
+#[cfg(unix)]
+fn stderr_terminal_size() -> Option<(terminal_size::Width, terminal_size::Height)> {
+    use std::os::unix::io::AsRawFd;
+    terminal_size::terminal_size_using_fd(io::stderr().as_raw_fd())
+}
+
+#[cfg(windows)]
+fn stderr_terminal_size() -> Option<(terminal_size::Width, terminal_size::Height)> {
+    use std::os::windows::io::AsRawHandle;
+    terminal_size::terminal_size_using_handle(io::stderr().as_raw_handle())
+}
+
+#[cfg(not(any(unix, windows)))]
+fn stderr_terminal_size() -> Option<(terminal_size::Width, terminal_size::Height)> {
+    None
+}
+
+/// Adapter, which allows writing to a writer shared behind a mutex.
+pub(crate) struct SharedWriter<W>(pub(crate) Arc<Mutex<W>>);
+
+impl<W: Write> Write for SharedWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.lock()?.write(buf)
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        self.lock()?.write_all(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.lock()?.flush()
+    }
+}
+
+impl<W> SharedWriter<W> {
+    fn lock(&self) -> io::Result<std::sync::MutexGuard<'_, W>> {
+        self.0
+            .lock()
+            .map_err(|_| io::Error::other("shared writer mutex poisoned"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use termcolor::{Ansi, Color, ColorSpec};
+
+    #[test]
+    fn color_writer() {
+        let render = |color_mode| {
+            let written = Arc::new(Mutex::new(Vec::new()));
+            let writer = Ansi::new(SharedWriter(written.clone()));
+            Output::ColorWriter(Box::new(writer))
+                .print(color_mode, |writer| {
+                    writer.set_color(ColorSpec::new().set_fg(Some(Color::Red)))?;
+                    write!(writer, "error")?;
+                    writer.reset()
+                })
+                .unwrap();
+            let written = written.lock().unwrap().clone();
+            String::from_utf8(written).unwrap()
+        };
+
+        assert_eq!(
+            "\u{1b}[0m\u{1b}[31merror\u{1b}[0m",
+            render(ColorMode::Always)
+        );
+        assert_eq!("error", render(ColorMode::Never));
+    }
+}
@@ -1,17 +1,24 @@
 use crate::{
-    format::{format_duration, format_timing},
-    semantics::SemanticInfo,
+    buffer::{Dropped, Trace},
+    color::{ColorMode, Theme},
+    format::{
+        clip, display_width, format_duration, format_timestamp, format_timing, pad, truncate, wrap,
+    },
+    output::Output,
+    redact::Redaction,
+    semantics::{SemanticInfo, Summarizers},
+    stacktrace::{self, StackTraceOptions},
+    tree::TreeStyle,
 };
 use opentelemetry::{
-    sdk::export::trace::SpanData,
-    trace::{Event, SpanId, SpanKind},
+    sdk::{self, export::trace::SpanData},
+    trace::{Event, Link, SpanContext, SpanId, SpanKind, StatusCode, TraceId},
+    KeyValue,
 };
 use opentelemetry_semantic_conventions as semcov;
-use std::collections::HashMap;
-use std::io::Write;
+use std::collections::{HashMap, HashSet};
 use std::time::{Duration, SystemTime};
-use termcolor::{Buffer, BufferWriter, Color, ColorChoice, ColorSpec, WriteColor};
-use terminal_size::terminal_size;
+use termcolor::{ColorSpec, WriteColor};
 
 /// Number of whitespace characters between columns (e.g. between status and duration).
 const COLUMN_GAP: usize = 2;
@@ -29,6 +36,19 @@ const STATUS_WIDTH: usize = 3;
 /// long unit, e.g. 999ms.
 const DURATION_WIDTH: usize = 5;
 
+/// Controls whether and where attributes of events are printed. Exception events are not
+/// affected, they always show the exception type and message.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum EventAttributes {
+    /// Only print the name of events.
+    #[default]
+    Hidden,
+    /// Print attributes after the name of the event.
+    Inline,
+    /// Print attributes on the line following the event.
+    NextLine,
+}
+
 #[derive(Clone, Copy)]
 struct Columns {
     start_width: usize,
@@ -41,6 +61,7 @@ impl Columns {
     fn new(terminal_width: usize, timing_column_width: f64) -> Self {
         let status_width = STATUS_WIDTH + COLUMN_GAP;
         let duration_width = DURATION_WIDTH + COLUMN_GAP;
+        let terminal_width = terminal_width.max(MIN_START_WIDTH + status_width + duration_width);
         let timing_width = ((terminal_width as f64 * timing_column_width).round() as usize).clamp(
             0,
             terminal_width - MIN_START_WIDTH - status_width - duration_width,
@@ -52,6 +73,12 @@ impl Columns {
             timing_width,
         }
     }
+
+    /// Width of rows without status and duration, i.e. events, links and status messages. They
+    /// may use the status and duration columns.
+    fn message_width(&self) -> usize {
+        self.start_width + self.status_width + self.duration_width
+    }
 }
 
 struct TimingParent {
@@ -66,45 +93,57 @@ impl TimingParent {
     }
 }
 
-fn get_color(is_err: bool) -> ColorSpec {
-    let mut color = ColorSpec::new();
-    color.set_fg(if is_err { Some(Color::Red) } else { None });
-    color
-}
-
 struct PrintContext<'a> {
-    buffer: &'a mut Buffer,
+    buffer: &'a mut dyn WriteColor,
     columns: Columns,
     timing_parent: TimingParent,
+    theme: &'a Theme,
+    tree_style: TreeStyle,
+    indent_width: usize,
+    wrap_details: bool,
+    summarizers: &'a Summarizers,
+    /// Summaries of all spans in the trace, which are computed once up front.
+    summaries: &'a mut HashMap<SpanId, SemanticInfo<'static>>,
+    stack_traces: Option<&'a StackTraceOptions>,
+    event_attributes: EventAttributes,
+    /// Rows of all spans in the trace, which can be referenced by links.
+    link_targets: &'a HashMap<(TraceId, SpanId), String>,
 }
 
 impl<'a> PrintContext<'a> {
-    fn print_event(&mut self, event: Event, indent: usize) -> std::io::Result<()> {
+    fn print_event(&mut self, event: Event, levels: &[bool]) -> std::io::Result<()> {
+        let theme = self.theme;
+        let indent = self.indent(levels);
         let is_exception = event.name == "exception";
-        let message = if is_exception {
-            let exc_type = event
+        let attribute = |key| {
+            event
                 .attributes
                 .iter()
-                .find(|kv| kv.key == semcov::trace::EXCEPTION_TYPE)
-                .map_or_else(|| "unknown".into(), |kv| kv.value.as_str());
-            let exc_message = event
-                .attributes
-                .iter()
-                .find(|kv| kv.key == semcov::trace::EXCEPTION_MESSAGE)
-                .map_or_else(|| "".into(), |kv| kv.value.as_str());
-            format!("{}: {}", exc_type, exc_message)
+                .find(|kv| kv.key == key)
+                .map(|kv| kv.value.as_str())
+        };
+        let message = if is_exception {
+            let exc_type =
+                attribute(semcov::trace::EXCEPTION_TYPE).unwrap_or_else(|| "unknown".into());
+            let exc_message = attribute(semcov::trace::EXCEPTION_MESSAGE).unwrap_or_default();
+            // The exception left the scope of the span, i.e. it most likely caused the span to fail.
+            let escaped = attribute(semcov::trace::EXCEPTION_ESCAPED).is_some_and(|v| v == "true");
+            format!(
+                "{}{}: {}",
+                if escaped { "[escaped] " } else { "" },
+                exc_type,
+                exc_message
+            )
+        } else if self.event_attributes == EventAttributes::Inline && !event.attributes.is_empty() {
+            format!("{}  {}", event.name, format_attributes(&event.attributes))
         } else {
-            event.name.into_owned()
+            event.name.to_string()
         };
 
-        let mut start = format!(
-            "{indent}{message}",
-            indent = " ".repeat(indent),
-            message = message
-        );
-        start.truncate(
-            self.columns.start_width + self.columns.status_width + self.columns.duration_width,
-        );
+        let start_width = self.columns.message_width();
+        let indent = clip(&indent, start_width);
+        let message = truncate(&message, start_width - display_width(indent));
+        let start = pad(&format!("{}{}", indent, message), start_width);
 
         let timing = if self.columns.timing_width > COLUMN_GAP {
             format_timing(
@@ -119,42 +158,142 @@ impl<'a> PrintContext<'a> {
             "".into()
         };
 
-        self.buffer.set_color(&get_color(is_exception))?;
-        writeln!(
-            self.buffer,
-            "{start:start_width$}{timing:>timing_width$}",
-            start = start,
-            start_width =
-                self.columns.start_width + self.columns.status_width + self.columns.duration_width,
-            timing = timing,
-            timing_width = self.columns.timing_width
-        )
+        let row_style = if is_exception {
+            &theme.exception
+        } else {
+            &theme.event
+        };
+        self.write_part(row_style, row_style, &start)?;
+        self.write_part(
+            &theme.timing,
+            row_style,
+            &format!(
+                "{timing:>timing_width$}",
+                timing = timing,
+                timing_width = self.columns.timing_width
+            ),
+        )?;
+        self.end_line()?;
+
+        if !is_exception
+            && self.event_attributes == EventAttributes::NextLine
+            && !event.attributes.is_empty()
+        {
+            let (indent, width) = self.event_continuation(levels);
+            let attributes = format_attributes(&event.attributes);
+            let attributes = truncate(&attributes, width);
+            self.write_part(row_style, row_style, &format!("{}{}", indent, attributes))?;
+            self.end_line()?;
+        }
+
+        if let (true, Some(options), Some(stacktrace)) = (
+            is_exception,
+            self.stack_traces,
+            attribute(semcov::trace::EXCEPTION_STACKTRACE),
+        ) {
+            self.print_stack_trace(&stacktrace, options, levels, row_style)?;
+        }
+        Ok(())
     }
 
-    fn print_span(&mut self, span_data: &SpanData, indent: usize) -> std::io::Result<()> {
-        let kind = match span_data.span_kind {
-            SpanKind::Client => "CL",
-            SpanKind::Server => "SE",
-            SpanKind::Producer => "PR",
-            SpanKind::Consumer => "CO",
-            SpanKind::Internal => "IN",
-        };
+    /// Print the frames of a stack trace as continuation lines of an exception event.
+    fn print_stack_trace(
+        &mut self,
+        stacktrace: &str,
+        options: &StackTraceOptions,
+        levels: &[bool],
+        row_style: &ColorSpec,
+    ) -> std::io::Result<()> {
+        let theme = self.theme;
+        let (indent, line_width) = self.event_continuation(levels);
+        let indent = indent.as_str();
+
+        let frames = stacktrace::frames(stacktrace);
+        let shown = options.max_frames.unwrap_or(frames.len()).min(frames.len());
+        for frame in &frames[..shown] {
+            let style = if options.is_highlighted(frame) {
+                &theme.own_frame
+            } else {
+                &theme.frame
+            };
+            for (i, line) in frame.iter().enumerate() {
+                // Further lines of a frame, e.g. its location, are indented below its first line.
+                let line = if i == 0 {
+                    truncate(line, line_width)
+                } else {
+                    format!("  {}", truncate(line, line_width.saturating_sub(2))).into()
+                };
+                self.write_part(row_style, row_style, indent)?;
+                self.write_part(style, row_style, &line)?;
+                self.end_line()?;
+            }
+        }
+        if shown < frames.len() {
+            let omitted = frames.len() - shown;
+            let omitted = format!(
+                "… {} more {}",
+                omitted,
+                if omitted == 1 { "frame" } else { "frames" }
+            );
+            self.write_part(row_style, row_style, indent)?;
+            self.write_part(&theme.frame, row_style, &truncate(&omitted, line_width))?;
+            self.end_line()?;
+        }
+        Ok(())
+    }
+
+    fn print_span(
+        &mut self,
+        span_data: &SpanData,
+        levels: &[bool],
+        has_children: bool,
+    ) -> std::io::Result<()> {
+        let theme = self.theme;
+        let indent = self.indent(levels);
+        let kind = kind_badge(&span_data.span_kind);
 
         let SemanticInfo {
             name,
             details,
             is_err,
+            is_warn,
             status,
-        } = SemanticInfo::from(span_data);
-
-        let mut start = format!(
-            "{indent}{kind}  {name}  {details}",
-            indent = " ".repeat(indent),
-            kind = kind,
-            name = name,
-            details = details
+        } = match self.summaries.remove(&span_data.span_context.span_id()) {
+            Some(info) => info,
+            None => self.summarizers.summarize(span_data).into_owned(),
+        };
+
+        let indent = clip(&indent, self.columns.start_width);
+        let kind_width = self.columns.start_width - display_width(indent);
+        let kind = clip(kind, kind_width);
+        let rest_width = kind_width - display_width(kind);
+
+        // Continuation lines are indented to align with the name on the first line.
+        let continuation_indent = pad(
+            &self
+                .tree_style
+                .continuation_prefix(self.indent_width, levels, has_children),
+            display_width(indent) + 4,
         );
-        start.truncate(self.columns.start_width);
+        let continuation_indent = clip(&continuation_indent, self.columns.start_width);
+        let continuation_width = self.columns.start_width - display_width(continuation_indent);
+
+        let mut lines = if self.wrap_details {
+            wrap(
+                &format!("{}  {}", name, details),
+                rest_width.saturating_sub(2),
+                continuation_width,
+            )
+        } else {
+            vec![truncate(
+                &format!("{}  {}", name, details),
+                rest_width.saturating_sub(2),
+            )
+            .into_owned()]
+        }
+        .into_iter();
+        let first_line = lines.next().unwrap_or_default();
+        let rest = pad(clip(&format!("  {}", first_line), rest_width), rest_width);
 
         let duration = span_data
             .end_time
@@ -174,124 +313,1035 @@ impl<'a> PrintContext<'a> {
             "".into()
         };
 
-        self.buffer.set_color(&get_color(is_err))?;
-        writeln!(
-            self.buffer,
-            "{start:start_width$}{status:>status_width$}{duration:>duration_width$}{timing:>timing_width$}",
-            start = start,
-            start_width = self.columns.start_width,
-            status = status,
-            status_width = self.columns.status_width,
-            duration = format_duration(duration),
-            duration_width = self.columns.duration_width,
-            timing = timing,
-            timing_width = self.columns.timing_width
-        )
+        let plain = ColorSpec::new();
+        let row_style = if is_err {
+            &theme.error
+        } else if is_warn {
+            &theme.warning
+        } else {
+            &plain
+        };
+        self.write_part(row_style, row_style, indent)?;
+        self.write_part(theme.kind(&span_data.span_kind), row_style, kind)?;
+        self.write_part(
+            row_style,
+            row_style,
+            &format!(
+                "{rest}{status:>status_width$}",
+                rest = rest,
+                status = status,
+                status_width = self.columns.status_width,
+            ),
+        )?;
+        self.write_part(
+            &theme.duration,
+            row_style,
+            &format!(
+                "{duration:>duration_width$}",
+                duration = format_duration(duration),
+                duration_width = self.columns.duration_width,
+            ),
+        )?;
+        self.write_part(
+            &theme.timing,
+            row_style,
+            &format!(
+                "{timing:>timing_width$}",
+                timing = timing,
+                timing_width = self.columns.timing_width
+            ),
+        )?;
+        self.end_line()?;
+
+        for line in lines {
+            self.write_part(
+                row_style,
+                row_style,
+                &format!("{}{}", continuation_indent, line),
+            )?;
+            self.end_line()?;
+        }
+
+        // Show why the span failed. Instrumentations set the message together with the status.
+        if span_data.status_code == StatusCode::Error && !span_data.status_message.is_empty() {
+            let message = format!("error: {}", span_data.status_message);
+            // The message is indented like the continuation lines of the details.
+            let message_width = self.columns.message_width() - display_width(continuation_indent);
+            let message_lines = if self.wrap_details {
+                wrap(&message, message_width, message_width)
+            } else {
+                vec![truncate(&message, message_width).into_owned()]
+            };
+            for line in message_lines {
+                self.write_part(
+                    &theme.error,
+                    row_style,
+                    &format!("{}{}", continuation_indent, line),
+                )?;
+                self.end_line()?;
+            }
+        }
+
+        Ok(())
     }
+
+    fn print_link(&mut self, link: &Link, levels: &[bool]) -> std::io::Result<()> {
+        let theme = self.theme;
+        let indent = self.indent(levels);
+        let span_context = link.span_context();
+        let target = match self
+            .link_targets
+            .get(&(span_context.trace_id(), span_context.span_id()))
+        {
+            Some(row) => row.clone(),
+            None => format!(
+                "{:032x}/{:016x}",
+                span_context.trace_id(),
+                span_context.span_id()
+            ),
+        };
+        let mut message = format!("link to {}", target);
+        if !link.attributes().is_empty() {
+            message.push_str("  ");
+            message.push_str(&format_attributes(link.attributes()));
+        }
+
+        // Links don't have a time, so the timing column stays empty.
+        let width = self.columns.message_width();
+        let indent = clip(&indent, width);
+        let message = truncate(&message, width - display_width(indent));
+        let start = pad(&format!("{}{}", indent, message), width);
+        self.write_part(&theme.event, &theme.event, &start)?;
+        self.write_part(
+            &theme.event,
+            &theme.event,
+            &" ".repeat(self.columns.timing_width),
+        )?;
+        self.end_line()
+    }
+
+    /// Indent and remaining width of continuation lines of an event. Continuation lines are
+    /// indented two characters further than the event itself.
+    fn event_continuation(&self, levels: &[bool]) -> (String, usize) {
+        let width = self.columns.message_width();
+        let indent = self
+            .tree_style
+            .continuation_prefix(self.indent_width, levels, false);
+        let indent = pad(&indent, display_width(&self.indent(levels)) + 2);
+        let indent = clip(&indent, width).to_owned();
+        let remaining = width - display_width(&indent);
+        (indent, remaining)
+    }
+
+    fn indent(&self, levels: &[bool]) -> String {
+        self.tree_style.prefix(self.indent_width, levels)
+    }
+
+    /// Write part of a row using its own style or, if that's empty, the style of the row.
+    fn write_part(
+        &mut self,
+        style: &ColorSpec,
+        row_style: &ColorSpec,
+        text: &str,
+    ) -> std::io::Result<()> {
+        self.buffer
+            .set_color(if style.is_none() { row_style } else { style })?;
+        self.buffer.write_all(text.as_bytes())
+    }
+
+    fn end_line(&mut self) -> std::io::Result<()> {
+        self.buffer.reset()?;
+        writeln!(self.buffer)
+    }
+}
+
+fn kind_badge(kind: &SpanKind) -> &'static str {
+    match kind {
+        SpanKind::Client => "CL",
+        SpanKind::Server => "SE",
+        SpanKind::Producer => "PR",
+        SpanKind::Consumer => "CO",
+        SpanKind::Internal => "IN",
+    }
+}
+
+fn format_attributes(attributes: &[KeyValue]) -> String {
+    attributes
+        .iter()
+        .map(|kv| format!("{}={}", kv.key, kv.value))
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 enum Printable {
+    Link(Box<Link>),
     Event(Box<Event>),
     Span(Box<SpanData>),
 }
 
 impl Printable {
+    /// Merge child spans and events ordered by time. Links don't have a time and are put first.
     fn merge_lists(
         spans: impl IntoIterator<Item = SpanData>,
         events: impl IntoIterator<Item = Event>,
+        links: impl IntoIterator<Item = Link>,
     ) -> Vec<Printable> {
-        let mut merged: Vec<Printable> = spans
+        let mut merged: Vec<(SystemTime, Printable)> = spans
             .into_iter()
-            .map(|span| Printable::Span(Box::new(span)))
+            .map(|span| (span.start_time, Printable::Span(Box::new(span))))
             .chain(
                 events
                     .into_iter()
-                    .map(|event| Printable::Event(Box::new(event))),
+                    .map(|event| (event.timestamp, Printable::Event(Box::new(event)))),
             )
             .collect();
-        merged.sort_by_key(|x| match x {
-            Printable::Span(span) => span.start_time,
-            Printable::Event(event) => event.timestamp,
-        });
-        merged
+        merged.sort_by_key(|(time, _)| *time);
+        links
+            .into_iter()
+            .map(|link| Printable::Link(Box::new(link)))
+            .chain(merged.into_iter().map(|(_, printable)| printable))
+            .collect()
     }
 }
 
-struct PrintableTrace(HashMap<SpanId, Vec<SpanData>>);
+struct PrintableTrace {
+    spans: Trace,
+    /// Printed above the trace, e.g. to mark it as incomplete.
+    marker: Option<String>,
+    /// Summaries of placeholders for missing parents. Placeholders are not summarized like
+    /// exported spans and are not counted as spans of the trace.
+    placeholders: HashMap<SpanId, SemanticInfo<'static>>,
+    /// Parent span ids of root spans, whose parent is in another process. They are printed after
+    /// the details of the root.
+    remote_parents: HashMap<SpanId, SpanId>,
+}
 
 impl PrintableTrace {
-    fn new(trace: HashMap<SpanId, Vec<SpanData>>) -> Self {
-        Self(trace)
+    fn new(spans: Trace) -> Self {
+        Self {
+            spans,
+            marker: None,
+            placeholders: HashMap::new(),
+            remote_parents: HashMap::new(),
+        }
     }
 
     fn print(
         mut self,
-        buffer: &mut Buffer,
+        buffer: &mut dyn WriteColor,
         terminal_width: usize,
-        timing_column_width: f64,
+        options: &PrintOptions,
     ) -> std::io::Result<()> {
-        let columns = Columns::new(terminal_width, timing_column_width);
+        let columns = Columns::new(terminal_width, options.timing_column_width);
+        let placeholders = &self.placeholders;
+        let remote_parents = &self.remote_parents;
+        let mut summaries = self
+            .spans
+            .values()
+            .flatten()
+            .map(|span_data| {
+                let span_id = span_data.span_context.span_id();
+                let mut info = match placeholders.get(&span_id) {
+                    Some(info) => info.clone(),
+                    None => options.summarizers.summarize(span_data).into_owned(),
+                };
+                if let Some(parent_span_id) = remote_parents.get(&span_id) {
+                    let label = format!("remote parent {:016x}", parent_span_id);
+                    info.details = if info.details.is_empty() {
+                        label.into()
+                    } else {
+                        format!("{}  {}", info.details, label).into()
+                    };
+                }
+                (span_id, info)
+            })
+            .collect::<HashMap<_, _>>();
+        let summary = options.trace_header.then(|| self.summary(&summaries));
+        if let Some(summary) = &summary {
+            print_header_line(
+                buffer,
+                &options.theme.header,
+                terminal_width,
+                &summary.header(),
+            )?;
+        }
+        if let Some(marker) = &self.marker {
+            print_header_line(buffer, &options.theme.warning, terminal_width, marker)?;
+        }
+
+        let link_targets = self
+            .spans
+            .values()
+            .flatten()
+            .map(|span_data| {
+                let span_context = &span_data.span_context;
+                let name = summaries
+                    .get(&span_context.span_id())
+                    .map_or("", |info| info.name.as_ref());
+                let row = format!(
+                    "{}  {} ({:016x})",
+                    kind_badge(&span_data.span_kind),
+                    name,
+                    span_context.span_id()
+                );
+                ((span_context.trace_id(), span_context.span_id()), row)
+            })
+            .collect::<HashMap<_, _>>();
 
         let parent_span_id = SpanId::INVALID;
         let spans = self.consume_child_spans(parent_span_id);
         for span in spans {
-            let timing_parent = TimingParent::new(span.start_time, span.end_time);
+            let timing_parent = TimingParent::new(span.start_time, self.subtree_end(&span));
             let mut context = PrintContext {
                 buffer,
                 columns,
                 timing_parent,
+                theme: &options.theme,
+                tree_style: options.tree_style,
+                indent_width: options
+                    .indent_width
+                    .unwrap_or_else(|| options.tree_style.default_indent_width()),
+                wrap_details: options.wrap_details,
+                summarizers: &options.summarizers,
+                summaries: &mut summaries,
+                stack_traces: options.stack_traces.as_ref(),
+                event_attributes: options.event_attributes,
+                link_targets: &link_targets,
             };
-            self.print_span_tree(&mut context, span, 0)?;
+            self.print_span_tree(&mut context, span, &mut Vec::new())?;
+        }
+
+        if let Some(summary) = &summary {
+            print_header_line(
+                buffer,
+                &options.theme.header,
+                terminal_width,
+                &summary.footer(),
+            )?;
         }
 
         Ok(())
     }
 
+    fn summary(&self, summaries: &HashMap<SpanId, SemanticInfo<'static>>) -> TraceSummary {
+        let is_exported =
+            |span: &&SpanData| !self.placeholders.contains_key(&span.span_context.span_id());
+        let spans = self
+            .spans
+            .values()
+            .flatten()
+            .filter(is_exported)
+            .collect::<Vec<_>>();
+        let root = self
+            .spans
+            .get(&SpanId::INVALID)
+            .and_then(|roots| roots.iter().find(is_exported))
+            .or_else(|| spans.first().copied());
+        let resource_value = |key| {
+            root.and_then(|root| root.resource.as_ref())
+                .and_then(|resource| resource.get(key))
+                .map(|value| value.as_str().into_owned())
+        };
+        let start = spans.iter().map(|span| span.start_time).min();
+        let end = spans.iter().map(|span| span.end_time).max();
+        TraceSummary {
+            trace_id: root.map(|root| format!("{:032x}", root.span_context.trace_id())),
+            service_name: resource_value(semcov::resource::SERVICE_NAME),
+            service_version: resource_value(semcov::resource::SERVICE_VERSION),
+            start,
+            duration: start
+                .zip(end)
+                .and_then(|(start, end)| end.duration_since(start).ok())
+                .unwrap_or_default(),
+            span_count: spans.len(),
+            error_count: summaries.values().filter(|info| info.is_err).count(),
+        }
+    }
+
+    /// Latest end time in the tree below `root`. Children kept by the grace period may end after
+    /// their root, and the timing column is scaled so that they still fit.
+    fn subtree_end(&self, root: &SpanData) -> SystemTime {
+        let mut end = root.end_time;
+        let mut visited = HashSet::new();
+        let mut pending = vec![root.span_context.span_id()];
+        while let Some(span_id) = pending.pop() {
+            if !visited.insert(span_id) {
+                continue;
+            }
+            for child in self.spans.get(&span_id).into_iter().flatten() {
+                end = end.max(child.end_time);
+                pending.push(child.span_context.span_id());
+            }
+        }
+        end
+    }
+
     fn consume_child_spans(&mut self, parent_span_id: SpanId) -> Vec<SpanData> {
-        self.0.remove(&parent_span_id).unwrap_or_default()
+        self.spans.remove(&parent_span_id).unwrap_or_default()
     }
 
     fn print_span_tree(
         &mut self,
         context: &mut PrintContext,
         span_data: SpanData,
-        indent: usize,
+        levels: &mut Vec<bool>,
     ) -> std::io::Result<()> {
-        context.print_span(&span_data, indent)?;
-
         let child_spans = self.consume_child_spans(span_data.span_context.span_id());
-        let child_events = span_data.events;
-        let children = Printable::merge_lists(child_spans, child_events);
+        let has_children =
+            !child_spans.is_empty() || !span_data.events.is_empty() || !span_data.links.is_empty();
+        context.print_span(&span_data, levels, has_children)?;
+
+        let children = Printable::merge_lists(child_spans, span_data.events, span_data.links);
 
-        for child in children {
+        let children_count = children.len();
+        for (i, child) in children.into_iter().enumerate() {
+            levels.push(i + 1 == children_count);
             match child {
-                Printable::Span(span) => self.print_span_tree(context, *span, indent + 1)?,
-                Printable::Event(event) => context.print_event(*event, indent + 1)?,
+                Printable::Span(span) => self.print_span_tree(context, *span, levels)?,
+                Printable::Event(event) => context.print_event(*event, levels)?,
+                Printable::Link(link) => context.print_link(&link, levels)?,
             };
+            levels.pop();
         }
 
         Ok(())
     }
 }
 
-fn get_terminal_width() -> usize {
-    if let Some((terminal_size::Width(w), _)) = terminal_size() {
-        w as usize
+struct TraceSummary {
+    trace_id: Option<String>,
+    service_name: Option<String>,
+    service_version: Option<String>,
+    start: Option<SystemTime>,
+    duration: Duration,
+    span_count: usize,
+    error_count: usize,
+}
+
+impl TraceSummary {
+    /// E.g. `trace 4bf92f3577b34da6a3ce929d0e0e4736  checkout 1.2.0  2022-03-04T13:37:00.123Z`.
+    fn header(&self) -> String {
+        let service = match (&self.service_name, &self.service_version) {
+            (Some(name), Some(version)) => Some(format!("{} {}", name, version)),
+            (name, _) => name.clone(),
+        };
+        std::iter::once(format!(
+            "trace {}",
+            self.trace_id.as_deref().unwrap_or("unknown")
+        ))
+        .chain(service)
+        .chain(self.start.map(format_timestamp))
+        .collect::<Vec<_>>()
+        .join("  ")
+    }
+
+    /// E.g. `12 spans  1 error  350ms`.
+    fn footer(&self) -> String {
+        format!(
+            "{}  {}  {}",
+            plural(self.span_count, "span"),
+            plural(self.error_count, "error"),
+            format_duration(self.duration)
+        )
+    }
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    let suffix = if count == 1 { "" } else { "s" };
+    format!("{} {}{}", count, noun, suffix)
+}
+
+/// Print a line, which spans all columns.
+fn print_header_line(
+    buffer: &mut dyn WriteColor,
+    style: &ColorSpec,
+    terminal_width: usize,
+    text: &str,
+) -> std::io::Result<()> {
+    let text = truncate(text, terminal_width);
+    buffer.set_color(style)?;
+    buffer.write_all(text.as_bytes())?;
+    buffer.reset()?;
+    writeln!(buffer)
+}
+
+/// Options, which influence how traces are printed.
+#[derive(Clone, Debug)]
+pub(crate) struct PrintOptions {
+    pub(crate) timing_column_width: f64,
+    pub(crate) terminal_width: Option<usize>,
+    pub(crate) color_mode: ColorMode,
+    pub(crate) theme: Theme,
+    pub(crate) tree_style: TreeStyle,
+    pub(crate) indent_width: Option<usize>,
+    pub(crate) wrap_details: bool,
+    pub(crate) summarizers: Summarizers,
+    pub(crate) redaction: Redaction,
+    pub(crate) stack_traces: Option<StackTraceOptions>,
+    pub(crate) event_attributes: EventAttributes,
+    pub(crate) trace_header: bool,
+}
+
+impl Default for PrintOptions {
+    fn default() -> Self {
+        Self {
+            timing_column_width: 0.2,
+            terminal_width: None,
+            color_mode: ColorMode::default(),
+            theme: Theme::default(),
+            tree_style: TreeStyle::default(),
+            indent_width: None,
+            wrap_details: false,
+            summarizers: Summarizers::default(),
+            redaction: Redaction::default(),
+            stack_traces: None,
+            event_attributes: EventAttributes::default(),
+            trace_header: false,
+        }
+    }
+}
+
+/// Print how many traces and spans were dropped, if any.
+pub(crate) fn print_dropped(
+    dropped: Dropped,
+    output: &mut Output,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    if dropped.traces == 0 {
+        return Ok(());
+    }
+
+    let terminal_width = options
+        .terminal_width
+        .unwrap_or_else(|| output.terminal_width());
+    let message = format!(
+        "dropped {} ({}), buffer full",
+        plural(dropped.traces, "incomplete trace"),
+        plural(dropped.spans, "span")
+    );
+    output.print(options.color_mode, |buffer| {
+        print_header_line(buffer, &options.theme.warning, terminal_width, &message)
+    })
+}
+
+/// Print a trace, which may be missing its root span or other spans. Spans, whose parent is
+/// missing, are grouped under placeholder spans and the trace is marked as incomplete.
+///
+/// If `remote_parents_known` is set, spans with a remote parent were buffered as roots, so missing
+/// parents are known to be local spans, which have not been exported. Otherwise missing parents of
+/// server and consumer spans are assumed to be remote, because these spans usually handle requests
+/// of other services.
+pub(crate) fn print_buffered_trace(
+    trace_id: TraceId,
+    mut trace: Trace,
+    remote_parents_known: bool,
+    output: &mut Output,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    let span_ids: HashSet<_> = trace
+        .values()
+        .flatten()
+        .map(|span_data| span_data.span_context.span_id())
+        .collect();
+    let parent_span_ids = trace
+        .keys()
+        .cloned()
+        .filter(|x| *x != SpanId::INVALID && !span_ids.contains(x));
+    let mut placeholders: Vec<_> = parent_span_ids
+        .map(|parent_span_id| placeholder_parent(trace_id, parent_span_id, &trace[&parent_span_id]))
+        .collect();
+    // Roots are only buffered with a parent span id, if their parent is known to be remote.
+    let remote_parents = trace
+        .get(&SpanId::INVALID)
+        .into_iter()
+        .flatten()
+        .filter(|span_data| span_data.parent_span_id != SpanId::INVALID)
+        .map(|span_data| (span_data.span_context.span_id(), span_data.parent_span_id))
+        .collect();
+    if placeholders.is_empty() {
+        let mut printable = PrintableTrace::new(trace);
+        printable.remote_parents = remote_parents;
+        return print(printable, output, options);
+    }
+    placeholders.sort_by_key(|placeholder| placeholder.start_time);
+
+    // The span id alone is the name, which fits into narrow terminals. Details are truncated first.
+    let summaries = placeholders
+        .iter()
+        .map(|placeholder| {
+            let span_id = placeholder.span_context.span_id();
+            let handles_requests = |span_data: &SpanData| {
+                matches!(span_data.span_kind, SpanKind::Server | SpanKind::Consumer)
+            };
+            let details = if remote_parents_known {
+                "parent not exported"
+            } else if trace[&span_id].iter().all(handles_requests) {
+                "remote parent"
+            } else {
+                "parent missing"
+            };
+            let info = SemanticInfo::new(format!("{:016x}", span_id), details);
+            (span_id, info)
+        })
+        .collect();
+
+    let roots = trace.entry(SpanId::INVALID).or_default();
+    let marker = if roots.is_empty() {
+        "incomplete trace: the root span has not been exported yet"
     } else {
-        80
+        "incomplete trace: some parent spans have not been exported yet"
+    };
+    roots.extend(placeholders);
+
+    let mut printable = PrintableTrace::new(trace);
+    printable.marker = Some(marker.to_owned());
+    printable.placeholders = summaries;
+    printable.remote_parents = remote_parents;
+    print(printable, output, options)
+}
+
+/// Create a span, which stands in for the missing parent of `children`. It covers the time of its
+/// children.
+fn placeholder_parent(trace_id: TraceId, span_id: SpanId, children: &[SpanData]) -> SpanData {
+    let start_time = children
+        .iter()
+        .map(|span_data| span_data.start_time)
+        .min()
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+    let end_time = children
+        .iter()
+        .map(|span_data| span_data.end_time)
+        .max()
+        .unwrap_or(start_time);
+
+    SpanData {
+        span_context: SpanContext::new(
+            trace_id,
+            span_id,
+            /* trace_flags */ Default::default(),
+            /* is_remote */ false,
+            /* trace_trace */ Default::default(),
+        ),
+        parent_span_id: SpanId::INVALID,
+        span_kind: SpanKind::Internal,
+        name: "".into(),
+        start_time,
+        end_time,
+        attributes: sdk::trace::EvictedHashMap::new(0, 0),
+        events: sdk::trace::EvictedQueue::new(0),
+        links: sdk::trace::EvictedQueue::new(0),
+        status_code: StatusCode::Unset,
+        status_message: "".into(),
+        resource: None,
+        instrumentation_lib: sdk::InstrumentationLibrary::new("opentelemetry-stdout-tree", None),
     }
 }
 
-pub(crate) fn print_trace(
-    trace: HashMap<SpanId, Vec<SpanData>>,
-    timing_column_width: f64,
+/// Print spans, which were exported after the rest of their trace was printed. The trace must
+/// contain a single root, which is the late span whose parent was already printed.
+pub(crate) fn print_late_spans(
+    trace_id: TraceId,
+    trace: Trace,
+    output: &mut Output,
+    options: &PrintOptions,
+) -> std::io::Result<()> {
+    let mut printable = PrintableTrace::new(trace);
+    printable.marker = Some(format!("late spans for trace {:032x}", trace_id));
+    print(printable, output, options)
+}
+
+fn print(
+    mut trace: PrintableTrace,
+    output: &mut Output,
+    options: &PrintOptions,
 ) -> std::io::Result<()> {
-    let bufwtr = BufferWriter::stdout(ColorChoice::Auto);
-    let mut buffer = bufwtr.buffer();
+    for span_data in trace.spans.values_mut().flatten() {
+        options.redaction.redact_span(span_data);
+    }
+
+    let terminal_width = options
+        .terminal_width
+        .unwrap_or_else(|| output.terminal_width());
 
-    let terminal_width = get_terminal_width();
+    output.print(options.color_mode, |buffer| {
+        trace.print(buffer, terminal_width, options)?;
+        buffer.reset()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::SharedWriter;
+    use crate::testing::{span, span_id, trace_id};
+    use opentelemetry::sdk::Resource;
+    use pretty_assertions::assert_eq;
+    use std::sync::{Arc, Mutex};
+    use termcolor::Buffer;
+
+    fn render(spans: Vec<SpanData>, options: &PrintOptions, buffer: Buffer) -> String {
+        render_with_width(spans, options, buffer, 40)
+    }
+
+    fn render_with_width(
+        spans: Vec<SpanData>,
+        options: &PrintOptions,
+        mut buffer: Buffer,
+        width: usize,
+    ) -> String {
+        let mut trace: HashMap<SpanId, Vec<SpanData>> = HashMap::new();
+        for span in spans {
+            trace.entry(span.parent_span_id).or_default().push(span);
+        }
+        PrintableTrace::new(trace)
+            .print(&mut buffer, width, options)
+            .unwrap();
+        String::from_utf8(buffer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn theme_colors_row_by_status() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            ..Default::default()
+        };
+        let spans = vec![
+            span(1, 0, "root", vec![semcov::trace::HTTP_METHOD.string("GET")]),
+            span(
+                2,
+                1,
+                "404",
+                vec![
+                    semcov::trace::HTTP_METHOD.string("GET"),
+                    semcov::trace::HTTP_STATUS_CODE.i64(404),
+                ],
+            ),
+            span(
+                3,
+                1,
+                "503",
+                vec![
+                    semcov::trace::HTTP_METHOD.string("GET"),
+                    semcov::trace::HTTP_STATUS_CODE.i64(503),
+                ],
+            ),
+        ];
+        assert_eq!(
+            vec![
+                "SE  root  GET                   0   10ms",
+                " SE  404  GET                 404   10ms",
+                " SE  503  GET                 503   10ms",
+            ],
+            render(spans.clone(), &options, Buffer::no_color())
+                .lines()
+                .collect::<Vec<_>>()
+        );
 
-    PrintableTrace::new(trace).print(&mut buffer, terminal_width, timing_column_width)?;
-    bufwtr.print(&buffer)?;
-    Ok(())
+        let colored = render(spans, &options, Buffer::ansi());
+        let lines = colored.lines().collect::<Vec<_>>();
+        assert!(!lines[0].contains("\x1b[33m") && !lines[0].contains("\x1b[31m"));
+        assert!(lines[1].contains("\x1b[33m") && !lines[1].contains("\x1b[31m"));
+        assert!(lines[2].contains("\x1b[31m"));
+    }
+
+    #[test]
+    fn theme_styles_parts_independently() {
+        let mut theme = Theme::default();
+        theme.server.set_fg(Some(termcolor::Color::Green));
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            theme,
+            ..Default::default()
+        };
+        let spans = vec![span(1, 0, "root", vec![])];
+        assert_eq!(
+            "\x1b[0m\x1b[0m\x1b[32mSE\x1b[0m  root                        0\x1b[0m   10ms\x1b[0m\x1b[0m\n",
+            render(spans, &options, Buffer::ansi())
+        );
+    }
+
+    #[test]
+    fn tree_guides() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            tree_style: TreeStyle::Unicode,
+            ..Default::default()
+        };
+        let spans = vec![
+            span(1, 0, "root", vec![]),
+            span(2, 1, "a", vec![]),
+            span(3, 1, "b", vec![]),
+            span(4, 2, "c", vec![]),
+        ];
+        assert_eq!(
+            vec![
+                "SE  root                        0   10ms",
+                "├─ SE  a                        0   10ms",
+                "│  └─ SE  c                     0   10ms",
+                "└─ SE  b                        0   10ms",
+            ],
+            render(spans, &options, Buffer::no_color())
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn wrapped_details() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            tree_style: TreeStyle::Unicode,
+            wrap_details: true,
+            ..Default::default()
+        };
+        let spans = vec![
+            span(1, 0, "root", vec![]),
+            span(
+                2,
+                1,
+                "query",
+                vec![
+                    semcov::trace::DB_SYSTEM.string("postgresql"),
+                    semcov::trace::DB_STATEMENT
+                        .string("SELECT sess FROM session WHERE sid = $1 AND expire >= now()"),
+                ],
+            ),
+            span(3, 2, "child", vec![]),
+        ];
+        assert_eq!(
+            vec![
+                "SE  root                        0   10ms",
+                "└─ SE  query  SELECT sess       0   10ms",
+                "   │   FROM session WHERE",
+                "   │   sid = $1 AND expire",
+                "   │   >= now()",
+                "   └─ SE  child                 0   10ms",
+            ],
+            render(spans, &options, Buffer::no_color())
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn stack_traces() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            tree_style: TreeStyle::Unicode,
+            stack_traces: Some(StackTraceOptions {
+                max_frames: Some(2),
+                highlight: vec!["app::".to_owned()],
+            }),
+            ..Default::default()
+        };
+        let mut root = span(1, 0, "root", vec![]);
+        root.events.append_vec(&mut vec![Event::new(
+            "exception",
+            SystemTime::UNIX_EPOCH,
+            vec![
+                semcov::trace::EXCEPTION_TYPE.string("Error"),
+                semcov::trace::EXCEPTION_MESSAGE.string("boom"),
+                semcov::trace::EXCEPTION_ESCAPED.bool(true),
+                semcov::trace::EXCEPTION_STACKTRACE.string(
+                    "   0: app::run\n             at ./src/main.rs:3:5\n   1: std::rt::lang_start\n   2: main",
+                ),
+            ],
+            0,
+        )]);
+        let spans = vec![root, span(2, 1, "child", vec![])];
+        assert_eq!(
+            vec![
+                "SE  root                        0   10ms",
+                "├─ SE  child                    0   10ms",
+                "└─ [escaped] Error: boom                ",
+                "     0: app::run",
+                "       at ./src/main.rs:3:5",
+                "     1: std::rt::lang_start",
+                "     … 1 more frame",
+            ],
+            render(spans.clone(), &options, Buffer::no_color())
+                .lines()
+                .collect::<Vec<_>>()
+        );
+
+        // Frames of our own crate are bold.
+        let rendered = render(spans, &options, Buffer::ansi());
+        let line = |text| rendered.lines().find(|line| line.contains(text)).unwrap();
+        assert!(line("app::run").contains("\x1b[1m"));
+        assert!(!line("lang_start").contains("\x1b[1m"));
+    }
+
+    #[test]
+    fn event_attributes() {
+        let mut root = span(1, 0, "root", vec![]);
+        root.events.append_vec(&mut vec![Event::new(
+            "retry",
+            SystemTime::UNIX_EPOCH,
+            vec![
+                KeyValue::new("attempt", 2),
+                KeyValue::new("reason", "connection reset by peer"),
+            ],
+            0,
+        )]);
+        let render_with = |event_attributes| {
+            let options = PrintOptions {
+                timing_column_width: 0.0,
+                tree_style: TreeStyle::Unicode,
+                event_attributes,
+                redaction: Redaction::new().deny_key("reason"),
+                ..Default::default()
+            };
+            let mut root = root.clone();
+            options.redaction.redact_span(&mut root);
+            render(vec![root], &options, Buffer::no_color())
+        };
+
+        assert_eq!(
+            "SE  root                        0   10ms\n\
+             └─ retry                                \n",
+            render_with(EventAttributes::Hidden)
+        );
+        assert_eq!(
+            "SE  root                        0   10ms\n\
+             └─ retry  attempt=2 reason=***          \n",
+            render_with(EventAttributes::Inline)
+        );
+        assert_eq!(
+            "SE  root                        0   10ms\n\
+             └─ retry                                \n\
+             \x20    attempt=2 reason=***\n",
+            render_with(EventAttributes::NextLine)
+        );
+    }
+
+    #[test]
+    fn status_message() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            tree_style: TreeStyle::Unicode,
+            ..Default::default()
+        };
+        let mut root = span(1, 0, "root", vec![]);
+        root.status_code = StatusCode::Error;
+        root.status_message = "connection refused".into();
+        let mut child = span(2, 1, "child", vec![]);
+        child.status_code = StatusCode::Ok;
+        child.status_message = "ignored".into();
+        assert_eq!(
+            vec![
+                "SE  root                        2   10ms",
+                "│   error: connection refused",
+                "└─ SE  child                    1   10ms",
+            ],
+            render(vec![root, child], &options, Buffer::no_color())
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn links() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            tree_style: TreeStyle::Unicode,
+            ..Default::default()
+        };
+        let mut root = span(1, 0, "root", vec![]);
+        let child = span(2, 1, "child", vec![]);
+        let other_trace = |span_id| {
+            SpanContext::new(
+                trace_id(0xab),
+                span_id,
+                Default::default(),
+                true,
+                Default::default(),
+            )
+        };
+        root.links.append_vec(&mut vec![
+            Link::new(child.span_context.clone(), vec![]),
+            Link::new(other_trace(span_id(0xcd)), vec![KeyValue::new("batch", 1)]),
+            Link::new(other_trace(child.span_context.span_id()), vec![]),
+        ]);
+        assert_eq!(
+            vec![
+                "SE  root                                                      0   10ms",
+                "├─ link to SE  child (0000000000000002)                               ",
+                "├─ link to 000000000000000000000000000000ab/00000000000000cd  batch=1 ",
+                "├─ link to 000000000000000000000000000000ab/0000000000000002          ",
+                "└─ SE  child                                                  0   10ms",
+            ],
+            render_with_width(vec![root, child], &options, Buffer::no_color(), 70)
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn trace_header() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            trace_header: true,
+            ..Default::default()
+        };
+        let mut root = span(1, 0, "root", vec![]);
+        root.resource = Some(Arc::new(Resource::new(vec![
+            semcov::resource::SERVICE_NAME.string("checkout"),
+            semcov::resource::SERVICE_VERSION.string("1.2.0"),
+        ])));
+        let mut child = span(2, 1, "child", vec![]);
+        child.status_code = StatusCode::Error;
+        child.start_time += Duration::from_millis(5);
+        child.end_time += Duration::from_millis(5);
+        assert_eq!(
+            vec![
+                "trace 00000000000000000000000000000001  checkout 1.2.0  1970-01-01T00:00:00.000Z",
+                "SE  root                                                                          0   10ms",
+                " SE  child                                                                        2   10ms",
+                "2 spans  1 error  15ms",
+            ],
+            render_with_width(vec![root, child], &options, Buffer::no_color(), 90)
+                .lines()
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn placeholder_parents() {
+        let options = PrintOptions {
+            timing_column_width: 0.0,
+            terminal_width: Some(60),
+            ..Default::default()
+        };
+        let mut early = span(2, 1, "early", vec![]);
+        early.span_kind = SpanKind::Internal;
+        let mut late = span(3, 1, "late", vec![]);
+        late.span_kind = SpanKind::Internal;
+        late.start_time += Duration::from_millis(20);
+        late.end_time += Duration::from_millis(20);
+        let mut server = span(5, 4, "server", vec![]);
+        server.start_time -= Duration::from_millis(5);
+        server.end_time -= Duration::from_millis(5);
+        let mut trace = Trace::new();
+        for span_data in [early, late, server] {
+            trace
+                .entry(span_data.parent_span_id)
+                .or_default()
+                .push(span_data);
+        }
+
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let mut output = Output::Writer(Box::new(SharedWriter(written.clone())));
+        print_buffered_trace(trace_id(1), trace, true, &mut output, &options).unwrap();
+
+        let written = String::from_utf8(written.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            "incomplete trace: the root span has not been exported yet\n\
+             IN  0000000000000004  parent not exported           0   10ms\n \
+             SE  server                                         0   10ms\n\
+             IN  0000000000000001  parent not exported           0   30ms\n \
+             IN  early                                          0   10ms\n \
+             IN  late                                           0   10ms\n",
+            written
+        );
+    }
 }
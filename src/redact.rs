@@ -0,0 +1,350 @@
+use crate::semantics::SUMMARIZED_KEYS;
+use opentelemetry::{
+    sdk::{self, export::trace::SpanData},
+    trace::{Event, Link},
+    Array, Key, KeyValue, Value,
+};
+use opentelemetry_semantic_conventions as semcov;
+use regex::Regex;
+use std::{borrow::Cow, collections::HashSet};
+
+/// Replacement for redacted values.
+const MASK: &str = "***";
+
+/// Attributes, which contain URLs or paths with a query string.
+const URL_KEYS: [Key; 3] = [
+    semcov::trace::HTTP_URL,
+    semcov::trace::HTTP_TARGET,
+    Key::from_static_str("url.full"),
+];
+
+/// Attributes, which contain only the query string of a URL.
+const QUERY_KEYS: [Key; 1] = [Key::from_static_str("url.query")];
+
+/// Rules to remove sensitive data from printed traces.
+///
+/// Rules are applied to span and event names, attributes of spans, events and links and status
+/// messages before spans are summarized. This means they also apply to custom summarizers.
+///
+/// ```
+/// use opentelemetry_stdout_tree::{Redaction, Regex};
+///
+/// let redaction = Redaction::new()
+///     .deny_key("enduser.id")
+///     .mask_pattern(Regex::new(r"[\w.+-]+@[\w-]+\.[\w.]+").unwrap())
+///     .redact_urls(true);
+/// let _tracer = opentelemetry_stdout_tree::new_pipeline()
+///     .with_redaction(redaction)
+///     .install_simple();
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Redaction {
+    denied_keys: HashSet<Key>,
+    allowed_keys: Option<HashSet<Key>>,
+    summarized_keys: bool,
+    patterns: Vec<Regex>,
+    urls: bool,
+}
+
+impl Redaction {
+    /// Create rules, which don't redact anything.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Mask the value of attributes with this key.
+    pub fn deny_key(mut self, key: impl Into<Key>) -> Self {
+        self.denied_keys.insert(key.into());
+        self
+    }
+
+    /// Only print attributes with this key. Once a key was allowed, all other attributes are
+    /// removed, including the ones the built-in summarizers read, unless
+    /// [`Redaction::allow_summarized_keys`] is set.
+    pub fn allow_key(mut self, key: impl Into<Key>) -> Self {
+        self.allowed_keys
+            .get_or_insert_with(HashSet::new)
+            .insert(key.into());
+        self
+    }
+
+    /// Also allow all attributes the built-in summarizers read, e.g. `http.method`, if an allow
+    /// list is used. Off by default, because some of them, like `db.statement` or
+    /// `exception.message`, often contain sensitive data. Denied keys and patterns still apply.
+    pub fn allow_summarized_keys(mut self, allow_summarized_keys: bool) -> Self {
+        self.summarized_keys = allow_summarized_keys;
+        self
+    }
+
+    /// Mask all parts of names, attribute values and messages, which match the pattern.
+    pub fn mask_pattern(mut self, pattern: Regex) -> Self {
+        self.patterns.push(pattern);
+        self
+    }
+
+    /// Remove credentials and mask query parameter values of URLs in HTTP and URL attributes,
+    /// including `url.query`.
+    pub fn redact_urls(mut self, redact_urls: bool) -> Self {
+        self.urls = redact_urls;
+        self
+    }
+
+    fn is_empty(&self) -> bool {
+        self.denied_keys.is_empty()
+            && self.allowed_keys.is_none()
+            && self.patterns.is_empty()
+            && !self.urls
+    }
+
+    pub(crate) fn redact_span(&self, span_data: &mut SpanData) {
+        if self.is_empty() {
+            return;
+        }
+
+        span_data.name = self.redact_text(&span_data.name).into_owned().into();
+        span_data.status_message = self
+            .redact_text(&span_data.status_message)
+            .into_owned()
+            .into();
+
+        let attributes = std::mem::replace(
+            &mut span_data.attributes,
+            sdk::trace::EvictedHashMap::new(0, 0),
+        );
+        let attributes =
+            self.redact_attributes(attributes.into_iter().map(|(k, v)| KeyValue::new(k, v)));
+        span_data.attributes =
+            sdk::trace::EvictedHashMap::new(attributes.len() as u32, attributes.len());
+        for attribute in attributes {
+            span_data.attributes.insert(attribute);
+        }
+
+        let events = std::mem::replace(&mut span_data.events, sdk::trace::EvictedQueue::new(0));
+        let events = events
+            .into_iter()
+            .map(|event| Event {
+                name: self.redact_text(&event.name).into_owned().into(),
+                attributes: self.redact_attributes(event.attributes),
+                ..event
+            })
+            .collect::<Vec<_>>();
+        span_data.events = sdk::trace::EvictedQueue::new(events.len() as u32);
+        span_data.events.extend(events);
+
+        let links = std::mem::replace(&mut span_data.links, sdk::trace::EvictedQueue::new(0));
+        let links = links
+            .into_iter()
+            .map(|link| {
+                Link::new(
+                    link.span_context().clone(),
+                    self.redact_attributes(link.attributes().clone()),
+                )
+            })
+            .collect::<Vec<_>>();
+        span_data.links = sdk::trace::EvictedQueue::new(links.len() as u32);
+        span_data.links.extend(links);
+    }
+
+    fn redact_attributes(&self, attributes: impl IntoIterator<Item = KeyValue>) -> Vec<KeyValue> {
+        attributes
+            .into_iter()
+            .filter(|kv| {
+                self.allowed_keys.as_ref().is_none_or(|allowed| {
+                    allowed.contains(&kv.key)
+                        || (self.summarized_keys && SUMMARIZED_KEYS.contains(&kv.key))
+                })
+            })
+            .map(|kv| {
+                let value = self.redact_value(&kv.key, kv.value);
+                KeyValue::new(kv.key, value)
+            })
+            .collect()
+    }
+
+    fn redact_value(&self, key: &Key, value: Value) -> Value {
+        if self.denied_keys.contains(key) {
+            return MASK.into();
+        }
+
+        match value {
+            Value::String(s) => {
+                let s = if self.urls && URL_KEYS.contains(key) {
+                    redact_url(&s).into()
+                } else if self.urls && QUERY_KEYS.contains(key) {
+                    redact_query(&s).into()
+                } else {
+                    s
+                };
+                Value::String(self.redact_text(&s).into_owned().into())
+            }
+            Value::Array(Array::String(values)) => Value::Array(Array::String(
+                values
+                    .into_iter()
+                    .map(|s| self.redact_text(&s).into_owned().into())
+                    .collect(),
+            )),
+            value => value,
+        }
+    }
+
+    /// Apply all patterns to the text.
+    pub(crate) fn redact_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        let mut text = Cow::Borrowed(text);
+        for pattern in &self.patterns {
+            if let Cow::Owned(redacted) = pattern.replace_all(&text, MASK) {
+                text = Cow::Owned(redacted);
+            }
+        }
+        text
+    }
+}
+
+/// Remove user info and mask query parameter values of an absolute or relative URL.
+fn redact_url(url: &str) -> String {
+    let (url, fragment) = match url.find('#') {
+        Some(i) => url.split_at(i),
+        None => (url, ""),
+    };
+    let (url, query) = match url.find('?') {
+        Some(i) => (&url[..i], Some(&url[i + 1..])),
+        None => (url, None),
+    };
+
+    let mut redacted = match url.find("://") {
+        Some(scheme_end) => {
+            let authority_start = scheme_end + 3;
+            let authority_end = url[authority_start..]
+                .find('/')
+                .map_or(url.len(), |i| authority_start + i);
+            let host_start = url[authority_start..authority_end]
+                .rfind('@')
+                .map_or(authority_start, |i| authority_start + i + 1);
+            format!("{}{}", &url[..authority_start], &url[host_start..])
+        }
+        None => url.to_owned(),
+    };
+
+    if let Some(query) = query {
+        redacted.push('?');
+        redacted.push_str(&redact_query(query));
+    }
+    redacted.push_str(fragment);
+    redacted
+}
+
+/// Mask parameter values of a query string without the leading `?`.
+fn redact_query(query: &str) -> String {
+    query
+        .split('&')
+        .map(|param| match param.split_once('=') {
+            Some((name, _)) => format!("{}={}", name, MASK),
+            None => param.to_owned(),
+        })
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+    use pretty_assertions::assert_eq;
+    use std::time::SystemTime;
+    use test_case::test_case;
+
+    #[test_case("https://example.com/a", "https://example.com/a" ; "nothing to redact")]
+    #[test_case("https://user:pw@example.com/a", "https://example.com/a" ; "user info")]
+    #[test_case("https://example.com/a?token=abc&x", "https://example.com/a?token=***&x" ; "query")]
+    #[test_case("/a?token=abc#top", "/a?token=***#top" ; "relative with fragment")]
+    fn url(url: &str, expected: &str) {
+        assert_eq!(expected, redact_url(url));
+    }
+
+    #[test_case("url.query", "token=abc&x", "token=***&x" ; "query")]
+    #[test_case("url.full", "token=abc", "token=abc" ; "no url")]
+    fn url_attributes(key: &'static str, value: &'static str, expected: &'static str) {
+        let redaction = Redaction::new().redact_urls(true);
+        let mut span_data = span(1, 0, "root", vec![Key::new(key).string(value)]);
+
+        redaction.redact_span(&mut span_data);
+
+        assert_eq!(
+            Some(expected.into()),
+            span_data.attributes.get(&Key::new(key)).cloned()
+        );
+    }
+
+    #[test]
+    fn span_contents() {
+        let redaction = Redaction::new()
+            .deny_key("auth.token")
+            .mask_pattern(Regex::new(r"\w+@example\.com").unwrap())
+            .redact_urls(true);
+        let mut span_data = span(
+            1,
+            0,
+            "login jane@example.com",
+            vec![
+                Key::new("auth.token").string("secret"),
+                Key::new("user").string("jane@example.com"),
+                semcov::trace::HTTP_URL.string("https://example.com/?token=secret"),
+                Key::new("attempt").i64(1),
+            ],
+        );
+        span_data.events.append_vec(&mut vec![Event::new(
+            "exception",
+            SystemTime::UNIX_EPOCH,
+            vec![semcov::trace::EXCEPTION_MESSAGE.string("unknown user jane@example.com")],
+            0,
+        )]);
+
+        redaction.redact_span(&mut span_data);
+
+        assert_eq!("login ***", span_data.name);
+        let value = |key: &'static str| span_data.attributes.get(&Key::new(key)).cloned();
+        assert_eq!(Some("***".into()), value("auth.token"));
+        assert_eq!(Some("***".into()), value("user"));
+        assert_eq!(
+            Some("https://example.com/?token=***".into()),
+            value("http.url")
+        );
+        assert_eq!(Some(1.into()), value("attempt"));
+        let event = span_data.events.iter().next().unwrap();
+        assert_eq!(
+            vec![semcov::trace::EXCEPTION_MESSAGE.string("unknown user ***")],
+            event.attributes
+        );
+    }
+
+    #[test_case(false, vec![("keep", Value::I64(1))] ; "strict")]
+    #[test_case(
+        true,
+        vec![("http.method", Value::from("GET")), ("keep", Value::I64(1))]
+        ; "summarized keys"
+    )]
+    fn allow_list(allow_summarized_keys: bool, expected: Vec<(&str, Value)>) {
+        let redaction = Redaction::new()
+            .allow_key("keep")
+            .allow_summarized_keys(allow_summarized_keys);
+        let mut span_data = span(
+            1,
+            0,
+            "root",
+            vec![
+                Key::new("keep").i64(1),
+                Key::new("drop").i64(2),
+                semcov::trace::HTTP_METHOD.string("GET"),
+            ],
+        );
+
+        redaction.redact_span(&mut span_data);
+
+        let mut attributes = span_data
+            .attributes
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect::<Vec<_>>();
+        attributes.sort_by_key(|(key, _)| *key);
+        assert_eq!(expected, attributes);
+    }
+}
@@ -1,105 +1,499 @@
-use opentelemetry::{sdk::export::trace::SpanData, trace::StatusCode, Value};
+use crate::{
+    matcher::SpanMatcher,
+    sql::{self, SqlOptions},
+};
+use opentelemetry::{
+    sdk::export::trace::SpanData,
+    trace::{SpanKind, StatusCode},
+    Key, Value,
+};
 use opentelemetry_semantic_conventions as semcov;
-use std::borrow::Cow;
+use std::{borrow::Cow, fmt, sync::Arc};
 use url::Url;
 
-pub(crate) struct SemanticInfo<'a> {
-    pub(crate) name: Cow<'a, str>,
-    pub(crate) details: Cow<'a, str>,
-    pub(crate) is_err: bool,
-    pub(crate) status: i64,
+// Attributes introduced after the version of the semantic conventions we depend on.
+const HTTP_REQUEST_METHOD: Key = Key::from_static_str("http.request.method");
+const HTTP_RESPONSE_STATUS_CODE: Key = Key::from_static_str("http.response.status_code");
+const URL_FULL: Key = Key::from_static_str("url.full");
+const URL_PATH: Key = Key::from_static_str("url.path");
+const SERVER_ADDRESS: Key = Key::from_static_str("server.address");
+const DB_SYSTEM_NAME: Key = Key::from_static_str("db.system.name");
+const DB_NAMESPACE: Key = Key::from_static_str("db.namespace");
+const DB_QUERY_TEXT: Key = Key::from_static_str("db.query.text");
+const DB_OPERATION_NAME: Key = Key::from_static_str("db.operation.name");
+const DB_COLLECTION_NAME: Key = Key::from_static_str("db.collection.name");
+const DB_RESPONSE_STATUS_CODE: Key = Key::from_static_str("db.response.status_code");
+const MESSAGING_DESTINATION_NAME: Key = Key::from_static_str("messaging.destination.name");
+const MESSAGING_MESSAGE_ID: Key = Key::from_static_str("messaging.message.id");
+const MESSAGING_BATCH_MESSAGE_COUNT: Key = Key::from_static_str("messaging.batch.message_count");
+
+/// Attributes of spans and events, which are read by the built-in summarizers and when printing
+/// exception events. Redaction can keep them, even if they are not allowed explicitly.
+pub(crate) const SUMMARIZED_KEYS: [Key; 42] = [
+    HTTP_REQUEST_METHOD,
+    semcov::trace::HTTP_METHOD,
+    URL_FULL,
+    semcov::trace::HTTP_URL,
+    SERVER_ADDRESS,
+    semcov::trace::HTTP_SERVER_NAME,
+    semcov::trace::HTTP_HOST,
+    semcov::trace::HTTP_ROUTE,
+    URL_PATH,
+    semcov::trace::HTTP_TARGET,
+    HTTP_RESPONSE_STATUS_CODE,
+    semcov::trace::HTTP_STATUS_CODE,
+    semcov::trace::RPC_SYSTEM,
+    semcov::trace::NET_PEER_NAME,
+    semcov::trace::NET_PEER_IP,
+    semcov::trace::RPC_SERVICE,
+    semcov::trace::RPC_METHOD,
+    semcov::trace::RPC_GRPC_STATUS_CODE,
+    DB_SYSTEM_NAME,
+    semcov::trace::DB_SYSTEM,
+    DB_NAMESPACE,
+    semcov::trace::DB_NAME,
+    DB_QUERY_TEXT,
+    semcov::trace::DB_STATEMENT,
+    DB_OPERATION_NAME,
+    semcov::trace::DB_OPERATION,
+    DB_COLLECTION_NAME,
+    semcov::trace::DB_SQL_TABLE,
+    semcov::trace::DB_MONGODB_COLLECTION,
+    semcov::trace::DB_CASSANDRA_TABLE,
+    DB_RESPONSE_STATUS_CODE,
+    semcov::trace::MESSAGING_SYSTEM,
+    MESSAGING_DESTINATION_NAME,
+    semcov::trace::MESSAGING_DESTINATION,
+    semcov::trace::MESSAGING_OPERATION,
+    MESSAGING_MESSAGE_ID,
+    semcov::trace::MESSAGING_MESSAGE_ID,
+    MESSAGING_BATCH_MESSAGE_COUNT,
+    semcov::trace::EXCEPTION_TYPE,
+    semcov::trace::EXCEPTION_MESSAGE,
+    semcov::trace::EXCEPTION_ESCAPED,
+    semcov::trace::EXCEPTION_STACKTRACE,
+];
+
+/// Summary of a span, which is printed as one row.
+///
+/// Create it with [`SemanticInfo::new`] and set the remaining fields as needed. More fields may be
+/// added in the future.
+#[derive(Clone, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SemanticInfo<'a> {
+    /// Name of the span, e.g. the host of an HTTP request.
+    pub name: Cow<'a, str>,
+    /// Details printed after the name, e.g. method and path of an HTTP request.
+    pub details: Cow<'a, str>,
+    /// Whether the span failed. The row is printed using the error style.
+    pub is_err: bool,
+    /// Whether the span has a warning, e.g. an HTTP 4xx status code. The row is printed using the
+    /// warning style, unless `is_err` is also set.
+    pub is_warn: bool,
+    /// Value of the status column, e.g. an HTTP status code.
+    pub status: i64,
 }
 
-impl<'a> From<&'a SpanData> for SemanticInfo<'a> {
-    fn from(span_data: &'a SpanData) -> Self {
-        get_http_span_semantic_info(span_data)
-            .or_else(|| get_db_span_semantic_info(span_data))
-            .unwrap_or_else(|| get_default_span_semantic_info(span_data))
+impl<'a> SemanticInfo<'a> {
+    /// Create a summary of a successful span without status.
+    pub fn new(name: impl Into<Cow<'a, str>>, details: impl Into<Cow<'a, str>>) -> Self {
+        Self {
+            name: name.into(),
+            details: details.into(),
+            is_err: false,
+            is_warn: false,
+            status: 0,
+        }
+    }
+
+    pub(crate) fn into_owned(self) -> SemanticInfo<'static> {
+        SemanticInfo {
+            name: self.name.into_owned().into(),
+            details: self.details.into_owned().into(),
+            is_err: self.is_err,
+            is_warn: self.is_warn,
+            status: self.status,
+        }
     }
 }
 
-fn get_http_span_semantic_info(span_data: &SpanData) -> Option<SemanticInfo> {
-    let method = span_data
-        .attributes
-        .get(&semcov::trace::HTTP_METHOD)?
-        .as_str();
+/// Summarizes spans, which follow specific attribute conventions.
+///
+/// Register summarizers with [`StdoutTreePipelineBuilder::with_summarizer`] or
+/// [`StdoutTreePipelineBuilder::with_fallback_summarizer`].
+///
+/// ```
+/// use opentelemetry::sdk::export::trace::SpanData;
+/// use opentelemetry::Key;
+/// use opentelemetry_stdout_tree::{SemanticInfo, SpanSummarizer};
+///
+/// #[derive(Debug)]
+/// struct JobSummarizer;
+///
+/// impl SpanSummarizer for JobSummarizer {
+///     fn summarize<'a>(&self, span_data: &'a SpanData) -> Option<SemanticInfo<'a>> {
+///         let queue = span_data.attributes.get(&Key::new("job.queue"))?;
+///         let mut info = SemanticInfo::new(queue.as_str(), span_data.name.clone());
+///         info.is_err = span_data.attributes.get(&Key::new("job.failed")).is_some();
+///         Some(info)
+///     }
+/// }
+///
+/// let _tracer = opentelemetry_stdout_tree::new_pipeline()
+///     .with_summarizer(JobSummarizer)
+///     .install_simple();
+/// ```
+///
+/// [`StdoutTreePipelineBuilder::with_summarizer`]: crate::StdoutTreePipelineBuilder::with_summarizer
+/// [`StdoutTreePipelineBuilder::with_fallback_summarizer`]: crate::StdoutTreePipelineBuilder::with_fallback_summarizer
+pub trait SpanSummarizer: Send + Sync {
+    /// Summarize the span or return `None` if the span isn't supported by this summarizer.
+    fn summarize<'a>(&self, span_data: &'a SpanData) -> Option<SemanticInfo<'a>>;
+}
 
-    let name = if let Some(url) = span_data.attributes.get(&semcov::trace::HTTP_URL) {
+/// Chain of summarizers. User provided summarizers are tried before or after the built-in ones,
+/// in the order they were registered. Spans nobody recognizes show all their attributes.
+#[derive(Clone, Default)]
+pub(crate) struct Summarizers {
+    pub(crate) before: Vec<Arc<dyn SpanSummarizer>>,
+    pub(crate) after: Vec<Arc<dyn SpanSummarizer>>,
+    pub(crate) sql: SqlOptions,
+    pub(crate) attributes: AttributeOptions,
+}
+
+/// Controls which attributes are shown by the default summarizer and in what order.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct AttributeOptions {
+    /// Attributes, which are shown first, in the given order, for spans matching the matcher.
+    pub(crate) important: Vec<(SpanMatcher, Vec<Key>)>,
+    /// Whether to hide attributes, which aren't important.
+    pub(crate) hide_others: bool,
+}
+
+impl Summarizers {
+    pub(crate) fn summarize<'a>(&self, span_data: &'a SpanData) -> SemanticInfo<'a> {
+        self.before
+            .iter()
+            .find_map(|summarizer| summarizer.summarize(span_data))
+            .or_else(|| get_rpc_span_semantic_info(span_data))
+            .or_else(|| get_http_span_semantic_info(span_data))
+            .or_else(|| get_db_span_semantic_info(span_data, &self.sql))
+            .or_else(|| get_messaging_span_semantic_info(span_data))
+            .or_else(|| {
+                self.after
+                    .iter()
+                    .find_map(|summarizer| summarizer.summarize(span_data))
+            })
+            .unwrap_or_else(|| get_default_span_semantic_info(span_data, &self.attributes))
+    }
+}
+
+impl fmt::Debug for Summarizers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Summarizers")
+            .field("before", &self.before.len())
+            .field("after", &self.after.len())
+            .field("sql", &self.sql)
+            .field("attributes", &self.attributes)
+            .finish()
+    }
+}
+
+fn get_http_span_semantic_info(span_data: &SpanData) -> Option<SemanticInfo<'_>> {
+    let method = attribute(
+        span_data,
+        &[HTTP_REQUEST_METHOD, semcov::trace::HTTP_METHOD],
+    )?
+    .as_str();
+    let url = attribute(span_data, &[URL_FULL, semcov::trace::HTTP_URL]);
+
+    let name = if let Some(url) = url {
         Url::parse(&url.as_str())
             .ok()?
             .host_str()
             .unwrap_or("")
             .to_owned()
             .into()
-    } else if let Some(server_name) = span_data.attributes.get(&semcov::trace::HTTP_SERVER_NAME) {
+    } else if let Some(server_name) = attribute(
+        span_data,
+        &[
+            SERVER_ADDRESS,
+            semcov::trace::HTTP_SERVER_NAME,
+            semcov::trace::HTTP_HOST,
+        ],
+    ) {
         server_name.as_str()
-    } else if let Some(host) = span_data.attributes.get(&semcov::trace::HTTP_HOST) {
-        host.as_str()
     } else {
         span_data.name.clone()
     };
 
-    let path = if let Some(url) = span_data.attributes.get(&semcov::trace::HTTP_URL) {
+    let path = if let Some(url) = url {
         Url::parse(&url.as_str()).ok()?.path().to_owned().into()
-    } else if let Some(route) = span_data.attributes.get(&semcov::trace::HTTP_ROUTE) {
-        route.as_str()
-    } else if let Some(target) = span_data.attributes.get(&semcov::trace::HTTP_TARGET) {
-        target.as_str()
+    } else if let Some(path) = attribute(
+        span_data,
+        &[
+            semcov::trace::HTTP_ROUTE,
+            URL_PATH,
+            semcov::trace::HTTP_TARGET,
+        ],
+    ) {
+        path.as_str()
     } else {
         "".into()
     };
 
-    let status_code = span_data
-        .attributes
-        .get(&semcov::trace::HTTP_STATUS_CODE)
-        .and_then(|v| match v {
-            Value::I64(v) => Some(*v),
-            Value::F64(v) => Some(*v as i64),
-            Value::String(v) => v.parse::<i64>().ok(),
-            _ => None,
-        });
-
-    let is_err = status_code
-        .map(|status_code| status_code >= 400)
-        .unwrap_or(span_data.status_code == StatusCode::Error);
+    let status_code = attribute(
+        span_data,
+        &[HTTP_RESPONSE_STATUS_CODE, semcov::trace::HTTP_STATUS_CODE],
+    )
+    .and_then(value_as_i64);
+
+    let is_err = status_code.is_some_and(|status_code| status_code >= 500)
+        || span_data.status_code == StatusCode::Error;
+    let is_warn = status_code.is_some_and(|status_code| (400..500).contains(&status_code));
 
     Some(SemanticInfo {
         name,
         details: format!("{} {}", method, path).into(),
         is_err,
+        is_warn,
+        status: status_code.unwrap_or(0),
+    })
+}
+
+fn get_rpc_span_semantic_info(span_data: &SpanData) -> Option<SemanticInfo<'_>> {
+    span_data.attributes.get(&semcov::trace::RPC_SYSTEM)?;
+
+    let name = if let Some(peer_name) = span_data.attributes.get(&semcov::trace::NET_PEER_NAME) {
+        peer_name.as_str()
+    } else if let Some(peer_ip) = span_data.attributes.get(&semcov::trace::NET_PEER_IP) {
+        peer_ip.as_str()
+    } else {
+        span_data.name.clone()
+    };
+
+    let service = span_data.attributes.get(&semcov::trace::RPC_SERVICE);
+    let method = span_data.attributes.get(&semcov::trace::RPC_METHOD);
+    let operation = match (service, method) {
+        (Some(service), Some(method)) => format!("{}/{}", service, method),
+        (Some(service), None) => service.to_string(),
+        (None, Some(method)) => method.to_string(),
+        (None, None) => span_data.name.to_string(),
+    };
+
+    let status_code = span_data
+        .attributes
+        .get(&semcov::trace::RPC_GRPC_STATUS_CODE)
+        .and_then(value_as_i64);
+
+    // The status column is too narrow for symbolic names, so they're added to the details.
+    let details = match status_code
+        .filter(|code| *code != 0)
+        .and_then(grpc_status_name)
+    {
+        Some(status_name) => format!("{}  {}", operation, status_name),
+        None => operation,
+    };
+
+    Some(SemanticInfo {
+        name,
+        details: details.into(),
+        is_err: status_code.is_some_and(|code| code != 0)
+            || span_data.status_code == StatusCode::Error,
+        is_warn: false,
         status: status_code.unwrap_or(0),
     })
 }
 
-fn get_db_span_semantic_info(span_data: &SpanData) -> Option<SemanticInfo> {
-    span_data.attributes.get(&semcov::trace::DB_SYSTEM)?;
+/// Symbolic name of a gRPC status code other than OK.
+///
+/// See https://github.com/grpc/grpc/blob/master/doc/statuscodes.md
+fn grpc_status_name(code: i64) -> Option<&'static str> {
+    Some(match code {
+        1 => "CANCELLED",
+        2 => "UNKNOWN",
+        3 => "INVALID_ARGUMENT",
+        4 => "DEADLINE_EXCEEDED",
+        5 => "NOT_FOUND",
+        6 => "ALREADY_EXISTS",
+        7 => "PERMISSION_DENIED",
+        8 => "RESOURCE_EXHAUSTED",
+        9 => "FAILED_PRECONDITION",
+        10 => "ABORTED",
+        11 => "OUT_OF_RANGE",
+        12 => "UNIMPLEMENTED",
+        13 => "INTERNAL",
+        14 => "UNAVAILABLE",
+        15 => "DATA_LOSS",
+        16 => "UNAUTHENTICATED",
+        _ => return None,
+    })
+}
+
+/// Value of the first of the given attributes, which is set on the span. Used to support multiple
+/// generations of the semantic conventions.
+fn attribute<'a>(span_data: &'a SpanData, keys: &[Key]) -> Option<&'a Value> {
+    keys.iter().find_map(|key| span_data.attributes.get(key))
+}
+
+fn value_as_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::I64(v) => Some(*v),
+        Value::F64(v) => Some(*v as i64),
+        Value::String(v) => v.parse::<i64>().ok(),
+        _ => None,
+    }
+}
+
+fn get_db_span_semantic_info<'a>(
+    span_data: &'a SpanData,
+    sql_options: &SqlOptions,
+) -> Option<SemanticInfo<'a>> {
+    let system = attribute(span_data, &[DB_SYSTEM_NAME, semcov::trace::DB_SYSTEM])?.as_str();
 
-    let name = if let Some(name) = span_data.attributes.get(&semcov::trace::DB_NAME) {
+    let name = if let Some(name) = attribute(span_data, &[DB_NAMESPACE, semcov::trace::DB_NAME]) {
         name.as_str()
     } else {
         span_data.name.clone()
     };
 
-    let details = if let Some(statement) = span_data.attributes.get(&semcov::trace::DB_STATEMENT) {
-        statement.as_str()
-    } else if let Some(operation) = span_data.attributes.get(&semcov::trace::DB_OPERATION) {
+    // Statements of other databases, e.g. MongoDB queries, can't be masked, so they're hidden.
+    let is_sql = sql::is_sql(&system);
+    let statement = attribute(span_data, &[DB_QUERY_TEXT, semcov::trace::DB_STATEMENT])
+        .filter(|_| is_sql || !sql_options.mask_literals);
+    let details = if let Some(statement) = statement {
+        if is_sql {
+            sql::normalize(&statement.as_str(), &system, sql_options).into()
+        } else {
+            statement.as_str()
+        }
+    } else {
+        let operation = attribute(span_data, &[DB_OPERATION_NAME, semcov::trace::DB_OPERATION]);
+        let collection = attribute(
+            span_data,
+            &[
+                DB_COLLECTION_NAME,
+                semcov::trace::DB_SQL_TABLE,
+                semcov::trace::DB_MONGODB_COLLECTION,
+                semcov::trace::DB_CASSANDRA_TABLE,
+            ],
+        );
+        match (operation, collection) {
+            (Some(operation), Some(collection)) => format!("{} {}", operation, collection).into(),
+            (Some(operation), None) => operation.as_str(),
+            (None, Some(collection)) => collection.as_str(),
+            (None, None) => "".into(),
+        }
+    };
+
+    // Response status codes are strings, e.g. SQLSTATE codes like "42P01" or "23505". Short
+    // numeric ones, e.g. HTTP status codes of Cosmos DB, fit in the status column. Others are added
+    // to the details.
+    let response_status_code = span_data.attributes.get(&DB_RESPONSE_STATUS_CODE);
+    let (details, status) = match response_status_code {
+        Some(code) => match value_as_i64(code).filter(|_| code.as_str().len() <= 3) {
+            Some(code) => (details, code),
+            None if details.is_empty() => (code.as_str(), span_data.status_code as i64),
+            None => (
+                format!("{}  {}", details, code).into(),
+                span_data.status_code as i64,
+            ),
+        },
+        None => (details, span_data.status_code as i64),
+    };
+
+    Some(SemanticInfo {
+        name,
+        details,
+        is_err: span_data.status_code == StatusCode::Error,
+        is_warn: false,
+        status,
+    })
+}
+
+fn get_messaging_span_semantic_info(span_data: &SpanData) -> Option<SemanticInfo<'_>> {
+    let system = span_data
+        .attributes
+        .get(&semcov::trace::MESSAGING_SYSTEM)?
+        .as_str();
+
+    let name = if let Some(destination) = attribute(
+        span_data,
+        &[
+            MESSAGING_DESTINATION_NAME,
+            semcov::trace::MESSAGING_DESTINATION,
+        ],
+    ) {
+        destination.as_str()
+    } else {
+        span_data.name.clone()
+    };
+
+    let operation = if let Some(operation) = span_data
+        .attributes
+        .get(&semcov::trace::MESSAGING_OPERATION)
+    {
         operation.as_str()
+    } else if span_data.span_kind == SpanKind::Producer {
+        // Older conventions don't set an operation for sending messages.
+        "publish".into()
     } else {
         "".into()
     };
 
+    let mut details = vec![system.into_owned(), operation.into_owned()];
+    if let Some(message_id) = attribute(
+        span_data,
+        &[MESSAGING_MESSAGE_ID, semcov::trace::MESSAGING_MESSAGE_ID],
+    ) {
+        details.push(format!("id={}", message_id));
+    }
+    if let Some(batch_size) = span_data.attributes.get(&MESSAGING_BATCH_MESSAGE_COUNT) {
+        details.push(format!("batch={}", batch_size));
+    }
+    details.retain(|part| !part.is_empty());
+
     Some(SemanticInfo {
         name,
-        details,
+        details: details.join(" ").into(),
         is_err: span_data.status_code == StatusCode::Error,
+        is_warn: false,
         status: span_data.status_code as i64,
     })
 }
 
-fn get_default_span_semantic_info(span_data: &SpanData) -> SemanticInfo {
-    let details = span_data
-        .attributes
+fn get_default_span_semantic_info<'a>(
+    span_data: &'a SpanData,
+    attribute_options: &AttributeOptions,
+) -> SemanticInfo<'a> {
+    let mut important_keys: Vec<&Key> = Vec::new();
+    for (matcher, keys) in &attribute_options.important {
+        if matcher.matches(span_data) {
+            for key in keys {
+                if !important_keys.contains(&key) {
+                    important_keys.push(key);
+                }
+            }
+        }
+    }
+
+    let important = important_keys
         .iter()
+        .filter_map(|key| Some((*key, span_data.attributes.get(key)?)));
+    let mut others = if attribute_options.hide_others {
+        Vec::new()
+    } else {
+        span_data
+            .attributes
+            .iter()
+            .filter(|(k, _)| !important_keys.contains(k))
+            .collect::<Vec<_>>()
+    };
+    // Attributes are stored in a hash map. Sort them to print them in the same order every time.
+    others.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+    let details = important
+        .chain(others)
         .map(|(k, v)| format!("{}={}", k, v))
         .collect::<Vec<_>>()
         .join(" ");
@@ -108,6 +502,372 @@ fn get_default_span_semantic_info(span_data: &SpanData) -> SemanticInfo {
         name: span_data.name.clone(),
         details: details.into(),
         is_err: span_data.status_code == StatusCode::Error,
+        is_warn: false,
         status: span_data.status_code as i64,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+    use opentelemetry::KeyValue;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    struct QueueSummarizer;
+
+    impl SpanSummarizer for QueueSummarizer {
+        fn summarize<'a>(&self, span_data: &'a SpanData) -> Option<SemanticInfo<'a>> {
+            let queue = span_data.attributes.get(&Key::new("queue"))?;
+            Some(SemanticInfo {
+                name: queue.as_str(),
+                details: "job".into(),
+                is_err: false,
+                is_warn: false,
+                status: 1,
+            })
+        }
+    }
+
+    #[test_case(vec![], "grpc-span", "grpc-span", false, 0 ; "minimal")]
+    #[test_case(
+        vec![
+            semcov::trace::NET_PEER_NAME.string("greeter.svc"),
+            semcov::trace::RPC_SERVICE.string("helloworld.Greeter"),
+            semcov::trace::RPC_METHOD.string("SayHello"),
+            semcov::trace::RPC_GRPC_STATUS_CODE.i64(0),
+        ],
+        "greeter.svc",
+        "helloworld.Greeter/SayHello",
+        false,
+        0
+        ; "ok"
+    )]
+    #[test_case(
+        vec![
+            semcov::trace::NET_PEER_IP.string("10.0.0.1"),
+            semcov::trace::RPC_SERVICE.string("helloworld.Greeter"),
+            semcov::trace::RPC_METHOD.string("SayHello"),
+            semcov::trace::RPC_GRPC_STATUS_CODE.i64(14),
+        ],
+        "10.0.0.1",
+        "helloworld.Greeter/SayHello  UNAVAILABLE",
+        true,
+        14
+        ; "unavailable"
+    )]
+    fn rpc(
+        mut attributes: Vec<KeyValue>,
+        expected_name: &str,
+        expected_details: &str,
+        expected_is_err: bool,
+        expected_status: i64,
+    ) {
+        attributes.push(semcov::trace::RPC_SYSTEM.string("grpc"));
+        let span_data = span(1, 0, "grpc-span", attributes);
+        let info = Summarizers::default().summarize(&span_data);
+        assert_eq!(expected_name, info.name);
+        assert_eq!(expected_details, info.details);
+        assert_eq!(expected_is_err, info.is_err);
+        assert_eq!(expected_status, info.status);
+    }
+
+    #[test_case(SpanKind::Producer, vec![], "messaging-span", "kafka publish" ; "minimal producer")]
+    #[test_case(SpanKind::Consumer, vec![], "messaging-span", "kafka" ; "minimal consumer")]
+    #[test_case(
+        SpanKind::Consumer,
+        vec![semcov::trace::MESSAGING_MESSAGE_ID.string("m-1")],
+        "messaging-span",
+        "kafka id=m-1"
+        ; "no operation"
+    )]
+    #[test_case(
+        SpanKind::Consumer,
+        vec![
+            semcov::trace::MESSAGING_DESTINATION.string("orders"),
+            semcov::trace::MESSAGING_OPERATION.string("process"),
+            semcov::trace::MESSAGING_MESSAGE_ID.string("m-1"),
+        ],
+        "orders",
+        "kafka process id=m-1"
+        ; "old conventions"
+    )]
+    #[test_case(
+        SpanKind::Consumer,
+        vec![
+            MESSAGING_DESTINATION_NAME.string("orders"),
+            semcov::trace::MESSAGING_OPERATION.string("receive"),
+            MESSAGING_BATCH_MESSAGE_COUNT.i64(10),
+        ],
+        "orders",
+        "kafka receive batch=10"
+        ; "new conventions"
+    )]
+    fn messaging(
+        kind: SpanKind,
+        mut attributes: Vec<KeyValue>,
+        expected_name: &str,
+        expected_details: &str,
+    ) {
+        attributes.push(semcov::trace::MESSAGING_SYSTEM.string("kafka"));
+        let mut span_data = span(1, 0, "messaging-span", attributes);
+        span_data.span_kind = kind;
+        let info = Summarizers::default().summarize(&span_data);
+        assert_eq!(expected_name, info.name);
+        assert_eq!(expected_details, info.details);
+    }
+
+    #[test_case(
+        vec![
+            semcov::trace::HTTP_METHOD.string("GET"),
+            semcov::trace::HTTP_URL.string("https://example.com/books?id=1"),
+            semcov::trace::HTTP_STATUS_CODE.i64(404),
+        ]
+        ; "old client"
+    )]
+    #[test_case(
+        vec![
+            HTTP_REQUEST_METHOD.string("GET"),
+            URL_FULL.string("https://example.com/books?id=1"),
+            HTTP_RESPONSE_STATUS_CODE.i64(404),
+        ]
+        ; "stable client"
+    )]
+    #[test_case(
+        vec![
+            semcov::trace::HTTP_METHOD.string("GET"),
+            semcov::trace::HTTP_SERVER_NAME.string("example.com"),
+            semcov::trace::HTTP_TARGET.string("/books"),
+            semcov::trace::HTTP_STATUS_CODE.i64(404),
+        ]
+        ; "old server"
+    )]
+    #[test_case(
+        vec![
+            HTTP_REQUEST_METHOD.string("GET"),
+            SERVER_ADDRESS.string("example.com"),
+            URL_PATH.string("/books"),
+            HTTP_RESPONSE_STATUS_CODE.i64(404),
+        ]
+        ; "stable server"
+    )]
+    #[test_case(
+        vec![
+            HTTP_REQUEST_METHOD.string("GET"),
+            semcov::trace::HTTP_HOST.string("example.com"),
+            semcov::trace::HTTP_ROUTE.string("/books"),
+            semcov::trace::HTTP_STATUS_CODE.string("404"),
+        ]
+        ; "mixed"
+    )]
+    fn http(attributes: Vec<KeyValue>) {
+        let span_data = span(1, 0, "http-span", attributes);
+        let info = Summarizers::default().summarize(&span_data);
+        assert_eq!("example.com", info.name);
+        assert_eq!("GET /books", info.details);
+        assert_eq!(404, info.status);
+        assert!(info.is_warn && !info.is_err);
+    }
+
+    #[test_case(
+        vec![
+            semcov::trace::DB_SYSTEM.string("postgresql"),
+            semcov::trace::DB_NAME.string("shop"),
+            semcov::trace::DB_STATEMENT.string("SELECT * FROM books"),
+        ],
+        "shop",
+        "SELECT * FROM books",
+        0
+        ; "old statement"
+    )]
+    #[test_case(
+        vec![
+            DB_SYSTEM_NAME.string("postgresql"),
+            DB_NAMESPACE.string("shop"),
+            DB_QUERY_TEXT.string("SELECT * FROM books"),
+        ],
+        "shop",
+        "SELECT * FROM books",
+        0
+        ; "stable statement"
+    )]
+    #[test_case(
+        vec![
+            semcov::trace::DB_SYSTEM.string("mongodb"),
+            semcov::trace::DB_OPERATION.string("find"),
+            semcov::trace::DB_MONGODB_COLLECTION.string("books"),
+        ],
+        "db-span",
+        "find books",
+        0
+        ; "old collection"
+    )]
+    #[test_case(
+        vec![
+            DB_SYSTEM_NAME.string("mongodb"),
+            DB_OPERATION_NAME.string("find"),
+            DB_COLLECTION_NAME.string("books"),
+        ],
+        "db-span",
+        "find books",
+        0
+        ; "stable collection"
+    )]
+    #[test_case(
+        vec![
+            DB_SYSTEM_NAME.string("redis"),
+            DB_OPERATION_NAME.string("GET"),
+            DB_RESPONSE_STATUS_CODE.string("429"),
+        ],
+        "db-span",
+        "GET",
+        429
+        ; "short numeric response status code"
+    )]
+    #[test_case(
+        vec![
+            DB_SYSTEM_NAME.string("mongodb"),
+            DB_OPERATION_NAME.string("insert"),
+            DB_RESPONSE_STATUS_CODE.i64(11000),
+        ],
+        "db-span",
+        "insert  11000",
+        0
+        ; "long numeric response status code"
+    )]
+    #[test_case(
+        vec![
+            DB_SYSTEM_NAME.string("postgresql"),
+            DB_RESPONSE_STATUS_CODE.string("23505"),
+        ],
+        "db-span",
+        "23505",
+        0
+        ; "numeric sqlstate without details"
+    )]
+    #[test_case(
+        vec![
+            DB_SYSTEM_NAME.string("postgresql"),
+            semcov::trace::DB_SQL_TABLE.string("books"),
+            DB_RESPONSE_STATUS_CODE.string("42P01"),
+        ],
+        "db-span",
+        "books  42P01",
+        0
+        ; "symbolic response status code"
+    )]
+    fn db(
+        attributes: Vec<KeyValue>,
+        expected_name: &str,
+        expected_details: &str,
+        expected_status: i64,
+    ) {
+        let span_data = span(1, 0, "db-span", attributes);
+        let info = Summarizers::default().summarize(&span_data);
+        assert_eq!(expected_name, info.name);
+        assert_eq!(expected_details, info.details);
+        assert_eq!(expected_status, info.status);
+    }
+
+    #[test_case("mysql", "SELECT * FROM t WHERE name = \"Jo\"", "SELECT * FROM t WHERE name = ?" ; "sql")]
+    #[test_case("mongodb", "{\"email\": \"x@example.com\"}", "find users" ; "not sql")]
+    fn db_statement_masking(system: &str, statement: &str, expected_details: &str) {
+        let span_data = span(
+            1,
+            0,
+            "db-span",
+            vec![
+                semcov::trace::DB_SYSTEM.string(system.to_owned()),
+                semcov::trace::DB_STATEMENT.string(statement.to_owned()),
+                semcov::trace::DB_OPERATION.string("find"),
+                semcov::trace::DB_MONGODB_COLLECTION.string("users"),
+            ],
+        );
+        let summarizers = Summarizers {
+            sql: SqlOptions {
+                mask_literals: true,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(expected_details, summarizers.summarize(&span_data).details);
+    }
+
+    #[test_case(vec![], false, "a=1 b=2 c=3 d=4" ; "sorted")]
+    #[test_case(
+        vec![(SpanMatcher::new().name("job.*"), vec![Key::new("c"), Key::new("x"), Key::new("a")])],
+        false,
+        "c=3 a=1 b=2 d=4"
+        ; "important first"
+    )]
+    #[test_case(
+        vec![
+            (SpanMatcher::new().name("other"), vec![Key::new("b")]),
+            (SpanMatcher::new(), vec![Key::new("d")]),
+            (SpanMatcher::new().instrumentation_library("test"), vec![Key::new("c"), Key::new("d")]),
+        ],
+        false,
+        "d=4 c=3 a=1 b=2"
+        ; "matching rules combined"
+    )]
+    #[test_case(
+        vec![(SpanMatcher::new(), vec![Key::new("c")])],
+        true,
+        "c=3"
+        ; "others hidden"
+    )]
+    fn default_attributes(
+        important: Vec<(SpanMatcher, Vec<Key>)>,
+        hide_others: bool,
+        expected_details: &str,
+    ) {
+        let span_data = span(
+            1,
+            0,
+            "job.run",
+            vec![
+                Key::new("d").i64(4),
+                Key::new("b").i64(2),
+                Key::new("c").i64(3),
+                Key::new("a").i64(1),
+            ],
+        );
+        let summarizers = Summarizers {
+            attributes: AttributeOptions {
+                important,
+                hide_others,
+            },
+            ..Default::default()
+        };
+        assert_eq!(expected_details, summarizers.summarize(&span_data).details);
+    }
+
+    #[test]
+    fn summarizer_order() {
+        let span_data = span(
+            1,
+            0,
+            "root",
+            vec![
+                semcov::trace::HTTP_METHOD.string("GET"),
+                Key::new("queue").string("emails"),
+            ],
+        );
+
+        let before = Summarizers {
+            before: vec![Arc::new(QueueSummarizer)],
+            ..Default::default()
+        };
+        assert_eq!("emails", before.summarize(&span_data).name);
+
+        let after = Summarizers {
+            after: vec![Arc::new(QueueSummarizer)],
+            ..Default::default()
+        };
+        assert_eq!("root", after.summarize(&span_data).name);
+
+        let span_data = span(1, 0, "root", vec![Key::new("queue").string("emails")]);
+        assert_eq!("emails", after.summarize(&span_data).name);
+    }
+}
@@ -0,0 +1,312 @@
+/// Controls how SQL statements of database spans are compacted before they are printed.
+///
+/// All options are disabled by default, which prints statements verbatim. Statements are only
+/// compacted if the `db.system` of the span is a SQL database.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct SqlOptions {
+    /// Collapse whitespace, including newlines, into single spaces.
+    pub collapse_whitespace: bool,
+    /// Replace the column list of `SELECT` statements with an ellipsis, e.g.
+    /// `SELECT … FROM users WHERE id = $1`.
+    pub abbreviate_select_list: bool,
+    /// Replace string and number literals with `?` and comments with `/* ? */` to avoid printing
+    /// sensitive data. Double-quoted text is treated as a string, unless the database is known to
+    /// use double quotes for identifiers like ANSI SQL. Statements of databases, which don't use
+    /// SQL, e.g. MongoDB, are replaced by the operation and collection of the span.
+    pub mask_literals: bool,
+}
+
+impl SqlOptions {
+    /// Enable all options.
+    pub fn compact() -> Self {
+        Self {
+            collapse_whitespace: true,
+            abbreviate_select_list: true,
+            mask_literals: true,
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.collapse_whitespace || self.abbreviate_select_list || self.mask_literals
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TokenKind {
+    Whitespace,
+    Word,
+    Number,
+    String,
+    QuotedIdentifier,
+    Comment,
+    Punctuation,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Token<'a> {
+    kind: TokenKind,
+    text: &'a str,
+}
+
+impl<'a> Token<'a> {
+    fn is_keyword(&self, keyword: &str) -> bool {
+        self.kind == TokenKind::Word && self.text.eq_ignore_ascii_case(keyword)
+    }
+
+    fn is_punctuation(&self, c: &str) -> bool {
+        self.kind == TokenKind::Punctuation && self.text == c
+    }
+}
+
+/// Values of `db.system` and `db.system.name` of databases using SQL.
+///
+/// See https://opentelemetry.io/docs/specs/semconv/attributes-registry/db/
+const SQL_SYSTEMS: &[&str] = &[
+    "adabas",
+    "aws.redshift",
+    "cache",
+    "clickhouse",
+    "cloudscape",
+    "cockroachdb",
+    "db2",
+    "derby",
+    "edb",
+    "firebird",
+    "firstsql",
+    "gcp.spanner",
+    "h2",
+    "hanadb",
+    "hive",
+    "hsqldb",
+    "ibm.db2",
+    "informix",
+    "ingres",
+    "instantdb",
+    "interbase",
+    "mariadb",
+    "maxdb",
+    "microsoft.sql_server",
+    "mssql",
+    "mssqlcompact",
+    "mysql",
+    "netezza",
+    "oracle",
+    "oracle.db",
+    "other_sql",
+    "pervasive",
+    "pointbase",
+    "postgresql",
+    "progress",
+    "redshift",
+    "sap.hana",
+    "snowflake",
+    "spanner",
+    "sqlite",
+    "sybase",
+    "teradata",
+    "tidb",
+    "trino",
+    "vertica",
+];
+
+/// Databases, which use double quotes for identifiers like ANSI SQL. Others, e.g. MySQL in its
+/// default mode, use them for strings or are unknown, so double-quoted text is masked to be safe.
+const ANSI_QUOTES_SYSTEMS: &[&str] = &[
+    "aws.redshift",
+    "clickhouse",
+    "cockroachdb",
+    "db2",
+    "derby",
+    "firebird",
+    "h2",
+    "hanadb",
+    "hsqldb",
+    "ibm.db2",
+    "microsoft.sql_server",
+    "mssql",
+    "oracle",
+    "oracle.db",
+    "postgresql",
+    "redshift",
+    "sap.hana",
+    "snowflake",
+    "sqlite",
+    "trino",
+    "vertica",
+];
+
+/// Whether the database system uses SQL. Statements of other systems are not normalized.
+pub(crate) fn is_sql(db_system: &str) -> bool {
+    SQL_SYSTEMS.contains(&db_system)
+}
+
+/// Split a statement into tokens. This is not a full SQL lexer. It only knows enough to tell
+/// literals, comments, words and whitespace apart.
+fn tokenize(statement: &str, ansi_quotes: bool) -> Vec<Token<'_>> {
+    let mut tokens = Vec::new();
+    let mut chars = statement.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        let kind = if c.is_whitespace() {
+            while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {}
+            TokenKind::Whitespace
+        } else if c == '\'' || c == '"' || c == '`' {
+            // Quotes are escaped by doubling them, which looks like two adjacent literals. Since
+            // both end up in the same token, that's handled implicitly.
+            loop {
+                match chars.next() {
+                    Some((_, next)) if next == c => {
+                        if chars.next_if(|(_, next)| *next == c).is_none() {
+                            break;
+                        }
+                    }
+                    Some(_) => {}
+                    None => break,
+                }
+            }
+            if c == '\'' || (c == '"' && !ansi_quotes) {
+                TokenKind::String
+            } else {
+                TokenKind::QuotedIdentifier
+            }
+        } else if c == '-' && chars.next_if(|(_, next)| *next == '-').is_some() {
+            while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            TokenKind::Comment
+        } else if c == '/' && chars.next_if(|(_, next)| *next == '*').is_some() {
+            let mut previous = ' ';
+            for (_, next) in chars.by_ref() {
+                if previous == '*' && next == '/' {
+                    break;
+                }
+                previous = next;
+            }
+            TokenKind::Comment
+        } else if c.is_ascii_digit() {
+            while chars
+                .next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '.')
+                .is_some()
+            {}
+            TokenKind::Number
+        } else if c.is_alphabetic() || c == '_' || c == '$' || c == '@' {
+            while chars
+                .next_if(|(_, c)| c.is_alphanumeric() || *c == '_' || *c == '$')
+                .is_some()
+            {}
+            TokenKind::Word
+        } else {
+            TokenKind::Punctuation
+        };
+        let end = chars.peek().map_or(statement.len(), |(end, _)| *end);
+        tokens.push(Token {
+            kind,
+            text: &statement[start..end],
+        });
+    }
+    tokens
+}
+
+/// Index of the `FROM` keyword, which belongs to the `SELECT` at `select_index`.
+fn find_from(tokens: &[Token<'_>], select_index: usize) -> Option<usize> {
+    let mut depth = 0usize;
+    for (i, token) in tokens.iter().enumerate().skip(select_index + 1) {
+        if token.is_punctuation("(") {
+            depth += 1;
+        } else if token.is_punctuation(")") {
+            // Reached the end of a subquery without a FROM.
+            depth = depth.checked_sub(1)?;
+        } else if depth == 0 && token.is_keyword("FROM") {
+            return Some(i);
+        }
+    }
+    None
+}
+
+/// Compact the SQL statement according to the options. `db_system` tells how double quotes are
+/// used.
+pub(crate) fn normalize(statement: &str, db_system: &str, options: &SqlOptions) -> String {
+    if !options.is_enabled() {
+        return statement.to_owned();
+    }
+
+    let tokens = tokenize(statement, ANSI_QUOTES_SYSTEMS.contains(&db_system));
+    let mut normalized = String::with_capacity(statement.len());
+    let mut i = 0;
+    while i < tokens.len() {
+        let token = tokens[i];
+        i += 1;
+        match token.kind {
+            TokenKind::Whitespace if options.collapse_whitespace => {
+                if !normalized.is_empty() && i < tokens.len() {
+                    normalized.push(' ');
+                }
+            }
+            TokenKind::String | TokenKind::Number if options.mask_literals => normalized.push('?'),
+            TokenKind::Comment if options.mask_literals => normalized.push_str("/* ? */"),
+            TokenKind::Word if options.abbreviate_select_list && token.is_keyword("SELECT") => {
+                normalized.push_str(token.text);
+                if let Some(from) = find_from(&tokens, i - 1) {
+                    normalized.push_str(" … ");
+                    i = from;
+                }
+            }
+            _ => normalized.push_str(token.text),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    const ALL: SqlOptions = SqlOptions {
+        collapse_whitespace: true,
+        abbreviate_select_list: true,
+        mask_literals: true,
+    };
+    const WHITESPACE: SqlOptions = SqlOptions {
+        collapse_whitespace: true,
+        abbreviate_select_list: false,
+        mask_literals: false,
+    };
+    const SELECT_LIST: SqlOptions = SqlOptions {
+        collapse_whitespace: false,
+        abbreviate_select_list: true,
+        mask_literals: false,
+    };
+    const LITERALS: SqlOptions = SqlOptions {
+        collapse_whitespace: false,
+        abbreviate_select_list: false,
+        mask_literals: true,
+    };
+
+    #[test_case("SELECT a\n  FROM t", SqlOptions::default() => "SELECT a\n  FROM t" ; "disabled")]
+    #[test_case("  SELECT a,\n\tb\n  FROM t  ", WHITESPACE => "SELECT a, b FROM t" ; "whitespace")]
+    #[test_case("SELECT a, b FROM t WHERE id = $1", SELECT_LIST => "SELECT … FROM t WHERE id = $1" ; "select list")]
+    #[test_case("select distinct a from t", SELECT_LIST => "select … from t" ; "select list lowercase")]
+    #[test_case("SELECT count(*), (SELECT max(x) FROM u) FROM t", SELECT_LIST => "SELECT … FROM t" ; "select list with subquery")]
+    #[test_case("SELECT * FROM t WHERE id IN (SELECT id FROM u)", SELECT_LIST => "SELECT … FROM t WHERE id IN (SELECT … FROM u)" ; "nested select")]
+    #[test_case("SELECT 1", SELECT_LIST => "SELECT 1" ; "select without from")]
+    #[test_case("SELECT a FROM t WHERE x = (SELECT 1)", SELECT_LIST => "SELECT … FROM t WHERE x = (SELECT 1)" ; "subquery without from")]
+    #[test_case("WHERE name = 'O''Brien' AND age > 42.5", LITERALS => "WHERE name = ? AND age > ?" ; "literals")]
+    #[test_case("SELECT \"col1\", t2.c3 FROM t2 WHERE id = $1", LITERALS => "SELECT \"col1\", t2.c3 FROM t2 WHERE id = $1" ; "identifiers and placeholders")]
+    #[test_case("SELECT a,\n  b\nFROM users\nWHERE email = 'x@example.com'", ALL => "SELECT … FROM users WHERE email = ?" ; "all")]
+    #[test_case("SELECT a -- user 42\nFROM t /* x@example.com */", LITERALS => "SELECT a /* ? */\nFROM t /* ? */" ; "comments")]
+    #[test_case("SELECT a - -1, b/2 FROM t", LITERALS => "SELECT a - -?, b/? FROM t" ; "operators")]
+    #[test_case("SELECT a FROM t /* unterminated", LITERALS => "SELECT a FROM t /* ? */" ; "unterminated comment")]
+    fn normalized(statement: &str, options: SqlOptions) -> String {
+        normalize(statement, "postgresql", &options)
+    }
+
+    #[test_case("postgresql" => "SELECT \"name\" FROM t WHERE a = \"x\"" ; "ansi quotes")]
+    #[test_case("mysql" => "SELECT ? FROM t WHERE a = ?" ; "mysql")]
+    #[test_case("other_sql" => "SELECT ? FROM t WHERE a = ?" ; "unknown")]
+    fn double_quotes(db_system: &str) -> String {
+        normalize(
+            "SELECT \"name\" FROM t WHERE a = \"x\"",
+            db_system,
+            &LITERALS,
+        )
+    }
+}
@@ -0,0 +1,87 @@
+/// Controls how stack traces of exception events are printed.
+///
+/// ```
+/// use opentelemetry_stdout_tree::StackTraceOptions;
+///
+/// let mut options = StackTraceOptions::default();
+/// options.max_frames = Some(10);
+/// options.highlight.push("my_crate::".to_owned());
+/// let _tracer = opentelemetry_stdout_tree::new_pipeline()
+///     .with_stack_traces(options)
+///     .install_simple();
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StackTraceOptions {
+    /// Maximum number of frames to print. Omitted frames are summarized in a single line. All
+    /// frames are printed if this is `None`.
+    pub max_frames: Option<usize>,
+    /// Frames containing any of these strings, e.g. the name of your crate, are printed using the
+    /// `own_frame` style of the theme.
+    pub highlight: Vec<String>,
+}
+
+impl StackTraceOptions {
+    pub(crate) fn is_highlighted(&self, frame: &[&str]) -> bool {
+        self.highlight
+            .iter()
+            .any(|pattern| frame.iter().any(|line| line.contains(pattern.as_str())))
+    }
+}
+
+/// Whether the line starts a frame of a Rust backtrace, e.g. `  12: my_crate::main`.
+fn is_numbered(line: &str) -> bool {
+    line.split_once(':')
+        .is_some_and(|(n, _)| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Split a stack trace into frames. Every frame consists of one or more trimmed lines.
+///
+/// Rust backtraces number their frames and print the location on a separate line, so lines are
+/// grouped by number if there are any numbered lines. Otherwise, e.g. for Java or Python stack
+/// traces, every line is a frame.
+pub(crate) fn frames(stacktrace: &str) -> Vec<Vec<&str>> {
+    let lines = stacktrace
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>();
+    let numbered = lines.iter().any(|line| is_numbered(line));
+
+    let mut frames: Vec<Vec<&str>> = Vec::new();
+    for line in lines {
+        match frames.last_mut() {
+            Some(frame) if numbered && !is_numbered(line) && is_numbered(frame[0]) => {
+                frame.push(line)
+            }
+            _ => frames.push(vec![line]),
+        }
+    }
+    frames
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case("" => Vec::<Vec<&str>>::new() ; "empty")]
+    #[test_case(
+        "java.lang.Exception: boom\n\tat a.B.c(B.java:1)\n\tat a.B.main(B.java:5)\n"
+        => vec![vec!["java.lang.Exception: boom"], vec!["at a.B.c(B.java:1)"], vec!["at a.B.main(B.java:5)"]]
+        ; "one line per frame"
+    )]
+    #[test_case(
+        "   0: app::run\n             at ./src/main.rs:3:5\n   1: app::main\n\n  10: std::rt::lang_start\n"
+        => vec![vec!["0: app::run", "at ./src/main.rs:3:5"], vec!["1: app::main"], vec!["10: std::rt::lang_start"]]
+        ; "rust backtrace"
+    )]
+    #[test_case(
+        "stack backtrace:\n   0: app::run\n             at ./src/main.rs:3:5"
+        => vec![vec!["stack backtrace:"], vec!["0: app::run", "at ./src/main.rs:3:5"]]
+        ; "rust backtrace with header"
+    )]
+    fn split(stacktrace: &str) -> Vec<Vec<&str>> {
+        frames(stacktrace)
+    }
+}
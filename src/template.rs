@@ -0,0 +1,182 @@
+use crate::{
+    matcher::SpanMatcher,
+    semantics::{SemanticInfo, SpanSummarizer},
+};
+use opentelemetry::{sdk::export::trace::SpanData, trace::StatusCode, Key};
+
+/// Summarizes spans matching a [`SpanMatcher`] by filling templates with their attributes.
+///
+/// Placeholders like `{cache.key}` are replaced with the value of the attribute. Missing
+/// attributes are replaced with nothing. Use `{{` and `}}` to print literal braces. Spans with
+/// an error status are printed as errors, just like spans summarized by the built-in
+/// summarizers.
+///
+/// ```
+/// use opentelemetry_stdout_tree::{SpanMatcher, SpanTemplate};
+///
+/// let template = SpanTemplate::new(
+///     SpanMatcher::new().has_attribute("cache.system"),
+///     "{cache.op} {cache.key}",
+/// )
+/// .name("{cache.system}");
+/// let _tracer = opentelemetry_stdout_tree::new_pipeline()
+///     .with_template(template)
+///     .install_simple();
+/// ```
+#[derive(Clone, Debug)]
+pub struct SpanTemplate {
+    matcher: SpanMatcher,
+    name: Option<Template>,
+    details: Template,
+}
+
+impl SpanTemplate {
+    /// Create a template for the details of spans matching the matcher. The name of the span is
+    /// printed as is.
+    pub fn new(matcher: SpanMatcher, details: &str) -> Self {
+        Self {
+            matcher,
+            name: None,
+            details: Template::parse(details),
+        }
+    }
+
+    /// Replace the name of matching spans with this template.
+    pub fn name(mut self, name: &str) -> Self {
+        self.name = Some(Template::parse(name));
+        self
+    }
+}
+
+impl SpanSummarizer for SpanTemplate {
+    fn summarize<'a>(&self, span_data: &'a SpanData) -> Option<SemanticInfo<'a>> {
+        if !self.matcher.matches(span_data) {
+            return None;
+        }
+
+        Some(SemanticInfo {
+            name: match &self.name {
+                Some(name) => name.render(span_data).into(),
+                None => span_data.name.clone(),
+            },
+            details: self.details.render(span_data).into(),
+            is_err: span_data.status_code == StatusCode::Error,
+            is_warn: false,
+            status: span_data.status_code as i64,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Part {
+    Text(String),
+    Attribute(Key),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Template(Vec<Part>);
+
+impl Template {
+    fn parse(template: &str) -> Self {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = template;
+        while let Some(i) = rest.find(['{', '}']) {
+            text.push_str(&rest[..i]);
+            let brace = &rest[i..i + 1];
+            rest = &rest[i + 1..];
+            if let Some(escaped) = rest.strip_prefix(brace) {
+                text.push_str(brace);
+                rest = escaped;
+            } else if let (Some(end), "{") = (rest.find('}'), brace) {
+                if !text.is_empty() {
+                    parts.push(Part::Text(std::mem::take(&mut text)));
+                }
+                parts.push(Part::Attribute(Key::new(rest[..end].to_owned())));
+                rest = &rest[end + 1..];
+            } else {
+                // Unmatched braces are printed as they are.
+                text.push_str(brace);
+            }
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Template(parts)
+    }
+
+    fn render(&self, span_data: &SpanData) -> String {
+        let mut rendered = String::new();
+        for part in &self.0 {
+            match part {
+                Part::Text(text) => rendered.push_str(text),
+                Part::Attribute(key) => {
+                    if let Some(value) = span_data.attributes.get(key) {
+                        rendered.push_str(&value.as_str());
+                    }
+                }
+            }
+        }
+        rendered
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::span;
+    use pretty_assertions::assert_eq;
+    use test_case::test_case;
+
+    #[test_case("{cache.op} {cache.key}", "get user:1"   ; "attributes")]
+    #[test_case("key={cache.key}!",       "key=user:1!"  ; "surrounding text")]
+    #[test_case("{cache.missing}|",       "|"            ; "missing attribute")]
+    #[test_case("{{{cache.op}}}",         "{get}"        ; "escaped braces")]
+    #[test_case("a } b {c",               "a } b {c"     ; "unmatched braces")]
+    #[test_case("{count}",                "3"            ; "number")]
+    fn render(template: &str, expected: &str) {
+        let span_data = span(
+            1,
+            0,
+            "cache",
+            vec![
+                Key::new("cache.op").string("get"),
+                Key::new("cache.key").string("user:1"),
+                Key::new("count").i64(3),
+            ],
+        );
+        assert_eq!(expected, Template::parse(template).render(&span_data));
+    }
+
+    #[test]
+    fn summarize() {
+        let template = SpanTemplate::new(
+            SpanMatcher::new().has_attribute("cache.system"),
+            "{cache.op}",
+        )
+        .name("{cache.system}");
+        let mut span_data = span(
+            1,
+            0,
+            "cache",
+            vec![
+                Key::new("cache.system").string("redis"),
+                Key::new("cache.op").string("get"),
+            ],
+        );
+        span_data.status_code = StatusCode::Error;
+
+        assert_eq!(
+            Some(SemanticInfo {
+                name: "redis".into(),
+                details: "get".into(),
+                is_err: true,
+                is_warn: false,
+                status: 2,
+            }),
+            template.summarize(&span_data)
+        );
+        assert_eq!(None, template.summarize(&span(2, 0, "other", vec![])));
+    }
+}
@@ -0,0 +1,49 @@
+use opentelemetry::{
+    sdk::{self, export::trace::SpanData},
+    trace::{SpanContext, SpanId, SpanKind, StatusCode, TraceId},
+    KeyValue,
+};
+use std::time::{Duration, SystemTime};
+
+pub(crate) fn span_id(id: u64) -> SpanId {
+    SpanId::from_bytes(id.to_be_bytes())
+}
+
+pub(crate) fn trace_id(id: u128) -> TraceId {
+    TraceId::from_bytes(id.to_be_bytes())
+}
+
+/// Create a server span in trace 1, which starts at the Unix epoch and takes 10ms.
+pub(crate) fn span(
+    id: u64,
+    parent_id: u64,
+    name: &'static str,
+    attributes: Vec<KeyValue>,
+) -> SpanData {
+    let start_time = SystemTime::UNIX_EPOCH;
+    let mut attribute_map = sdk::trace::EvictedHashMap::new(128, attributes.len());
+    for attribute in attributes {
+        attribute_map.insert(attribute);
+    }
+    SpanData {
+        span_context: SpanContext::new(
+            trace_id(1),
+            span_id(id),
+            Default::default(),
+            false,
+            Default::default(),
+        ),
+        parent_span_id: span_id(parent_id),
+        span_kind: SpanKind::Server,
+        name: name.into(),
+        start_time,
+        end_time: start_time + Duration::from_millis(10),
+        attributes: attribute_map,
+        events: sdk::trace::EvictedQueue::new(128),
+        links: sdk::trace::EvictedQueue::new(128),
+        status_code: StatusCode::Unset,
+        status_message: "".into(),
+        resource: None,
+        instrumentation_lib: sdk::InstrumentationLibrary::new("test", None),
+    }
+}
@@ -0,0 +1,144 @@
+/// Controls how the nesting of spans and events is displayed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum TreeStyle {
+    /// Indent children with spaces.
+    #[default]
+    Spaces,
+    /// Connect children to their parent with Unicode box-drawing characters (`├─`, `└─`, `│`).
+    Unicode,
+    /// Connect children to their parent with ASCII characters (`|-`, `` `- ``, `|`). Useful for
+    /// terminals, which can't display Unicode.
+    Ascii,
+}
+
+struct Glyphs {
+    branch: char,
+    last: char,
+    fill: char,
+    vertical: char,
+}
+
+const UNICODE_GLYPHS: Glyphs = Glyphs {
+    branch: '├',
+    last: '└',
+    fill: '─',
+    vertical: '│',
+};
+
+const ASCII_GLYPHS: Glyphs = Glyphs {
+    branch: '|',
+    last: '`',
+    fill: '-',
+    vertical: '|',
+};
+
+impl TreeStyle {
+    /// Default width of one indentation level.
+    pub(crate) fn default_indent_width(self) -> usize {
+        match self {
+            TreeStyle::Spaces => 1,
+            TreeStyle::Unicode | TreeStyle::Ascii => 3,
+        }
+    }
+
+    /// Build the prefix of a row. Every item in `levels` represents one level of nesting, starting
+    /// from the children of the root, and is `true` if the row or its ancestor on that level is
+    /// the last child of its parent.
+    pub(crate) fn prefix(self, indent_width: usize, levels: &[bool]) -> String {
+        let glyphs = match self {
+            TreeStyle::Spaces => return " ".repeat(indent_width * levels.len()),
+            TreeStyle::Unicode => &UNICODE_GLYPHS,
+            TreeStyle::Ascii => &ASCII_GLYPHS,
+        };
+        if indent_width == 0 {
+            return String::new();
+        }
+
+        let mut prefix = String::new();
+        if let Some((is_last, ancestors)) = levels.split_last() {
+            for is_ancestor_last in ancestors {
+                let first = if *is_ancestor_last {
+                    ' '
+                } else {
+                    glyphs.vertical
+                };
+                prefix.push(first);
+                prefix.push_str(&" ".repeat(indent_width - 1));
+            }
+
+            prefix.push(if *is_last { glyphs.last } else { glyphs.branch });
+            // Leave a gap between the connector and the content, if there's enough space.
+            let fill_width = if indent_width >= 3 {
+                indent_width - 2
+            } else {
+                indent_width - 1
+            };
+            prefix.push_str(&glyphs.fill.to_string().repeat(fill_width));
+            prefix.push_str(&" ".repeat(indent_width - 1 - fill_width));
+        }
+        prefix
+    }
+
+    /// Build the prefix of a continuation line, i.e. a line which belongs to the row described by
+    /// `levels` but doesn't start a new row. Guides continue down to the following siblings and,
+    /// if `has_children` is `true`, to the children of the row.
+    pub(crate) fn continuation_prefix(
+        self,
+        indent_width: usize,
+        levels: &[bool],
+        has_children: bool,
+    ) -> String {
+        let glyphs = match self {
+            TreeStyle::Spaces => return " ".repeat(indent_width * levels.len()),
+            TreeStyle::Unicode => &UNICODE_GLYPHS,
+            TreeStyle::Ascii => &ASCII_GLYPHS,
+        };
+        if indent_width == 0 {
+            return String::new();
+        }
+
+        let mut prefix = String::new();
+        for is_last in levels {
+            prefix.push(if *is_last { ' ' } else { glyphs.vertical });
+            prefix.push_str(&" ".repeat(indent_width - 1));
+        }
+        if has_children {
+            prefix.push(glyphs.vertical);
+        }
+        prefix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use test_case::test_case;
+
+    #[test_case(TreeStyle::Spaces,  1, &[]                  => ""          ; "spaces root")]
+    #[test_case(TreeStyle::Spaces,  2, &[false, true]       => "    "      ; "spaces nested")]
+    #[test_case(TreeStyle::Unicode, 3, &[]                  => ""          ; "unicode root")]
+    #[test_case(TreeStyle::Unicode, 3, &[false]             => "├─ "       ; "unicode branch")]
+    #[test_case(TreeStyle::Unicode, 3, &[true]              => "└─ "       ; "unicode last")]
+    #[test_case(TreeStyle::Unicode, 3, &[false, true, true] => "│     └─ " ; "unicode nested")]
+    #[test_case(TreeStyle::Unicode, 2, &[false, false]      => "│ ├─"      ; "unicode narrow")]
+    #[test_case(TreeStyle::Unicode, 1, &[false, true]       => "│└"        ; "unicode minimal")]
+    #[test_case(TreeStyle::Unicode, 0, &[false, true]       => ""          ; "unicode zero width")]
+    #[test_case(TreeStyle::Ascii,   4, &[false, true]       => "|   `-- "  ; "ascii nested")]
+    fn prefix(style: TreeStyle, indent_width: usize, levels: &[bool]) -> String {
+        style.prefix(indent_width, levels)
+    }
+
+    #[test_case(TreeStyle::Spaces,  1, &[false, true], true  => "  "      ; "spaces")]
+    #[test_case(TreeStyle::Unicode, 3, &[],            false => ""        ; "unicode root")]
+    #[test_case(TreeStyle::Unicode, 3, &[],            true  => "│"       ; "unicode root with children")]
+    #[test_case(TreeStyle::Unicode, 3, &[false, true], false => "│     "  ; "unicode nested")]
+    #[test_case(TreeStyle::Ascii,   2, &[false],       true  => "| |"     ; "ascii with children")]
+    fn continuation_prefix(
+        style: TreeStyle,
+        indent_width: usize,
+        levels: &[bool],
+        has_children: bool,
+    ) -> String {
+        style.continuation_prefix(indent_width, levels, has_children)
+    }
+}
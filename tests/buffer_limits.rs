@@ -0,0 +1,45 @@
+use opentelemetry::{
+    global,
+    trace::{Span as _, TraceContextExt as _, Tracer as _},
+    Context,
+};
+use opentelemetry_stdout_tree::EvictionPolicy;
+use pretty_assertions::assert_eq;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn dropped_traces() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let tracer = opentelemetry_stdout_tree::new_pipeline()
+        .with_shared_writer(output.clone())
+        .with_terminal_width(60)
+        .with_timing_column_width(0.0)
+        .with_max_buffered_traces(1)
+        .with_eviction_policy(EvictionPolicy::Drop)
+        .install_simple();
+
+    // The roots never end, so the children stay buffered until the next trace evicts them.
+    let mut roots = Vec::new();
+    for name in ["first", "second"] {
+        let cx = Context::new().with_span(tracer.start_with_context("root", &Context::new()));
+        tracer.start_with_context(name, &cx).end();
+        roots.push(cx);
+    }
+
+    global::shutdown_tracer_provider();
+    let parent_span_id = roots[1].span().span_context().span_id();
+
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        vec![
+            "dropped 1 incomplete trace (1 span), buffer full",
+            "incomplete trace: the root span has not been exported yet",
+            &format!(
+                "IN  {:016x}  parent not exported           0      0",
+                parent_span_id
+            ),
+            " IN  second                                         0      0",
+        ],
+        output.lines().collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,44 @@
+use opentelemetry::{
+    global,
+    trace::{Span as _, TraceContextExt as _, Tracer as _},
+    Context,
+};
+use pretty_assertions::assert_eq;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn force_flush_tracer_provider() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let tracer = opentelemetry_stdout_tree::new_pipeline()
+        .with_shared_writer(output.clone())
+        .with_terminal_width(60)
+        .with_timing_column_width(0.0)
+        .install_simple();
+    let take_output = || String::from_utf8(std::mem::take(&mut *output.lock().unwrap())).unwrap();
+
+    // The root doesn't end before flushing, so the child stays buffered.
+    let cx = Context::new().with_span(tracer.start_with_context("root", &Context::new()));
+    tracer.start_with_context("child", &cx).end();
+    assert_eq!("", take_output());
+
+    global::force_flush_tracer_provider();
+    let parent_span_id = cx.span().span_context().span_id();
+    assert_eq!(
+        vec![
+            "incomplete trace: the root span has not been exported yet".to_owned(),
+            format!(
+                "IN  {:016x}  parent not exported           0      0",
+                parent_span_id
+            ),
+            " IN  child                                          0      0".to_owned(),
+        ],
+        take_output().lines().collect::<Vec<_>>()
+    );
+
+    cx.span().end();
+    global::shutdown_tracer_provider();
+    assert_eq!(
+        "IN  root                                            0      0\n",
+        take_output()
+    );
+}
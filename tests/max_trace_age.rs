@@ -0,0 +1,47 @@
+use opentelemetry::{
+    global,
+    trace::{Span as _, SpanKind, TraceContextExt as _, Tracer as _},
+    Context,
+};
+use pretty_assertions::assert_eq;
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+#[test]
+fn incomplete_trace() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let tracer = opentelemetry_stdout_tree::new_pipeline()
+        .with_shared_writer(output.clone())
+        .with_terminal_width(60)
+        .with_timing_column_width(0.0)
+        .with_max_trace_age(Duration::ZERO)
+        .install_simple();
+
+    let root = tracer
+        .span_builder("root")
+        .with_kind(SpanKind::Server)
+        .start(&tracer);
+    let cx = Context::current_with_span(root);
+    let parent_span_id = cx.span().span_context().span_id();
+    tracer.start_with_context("child", &cx).end();
+    // The child is printed before the root ends, because the max trace age is reached immediately.
+    cx.span().end();
+
+    global::shutdown_tracer_provider();
+
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        vec![
+            "incomplete trace: the root span has not been exported yet",
+            &format!(
+                "IN  {:016x}  parent not exported           0      0",
+                parent_span_id
+            ),
+            " IN  child                                          0      0",
+            "SE  root                                            0      0",
+        ],
+        output.lines().collect::<Vec<_>>()
+    );
+}
@@ -0,0 +1,57 @@
+use opentelemetry::{
+    global,
+    trace::{
+        Span as _, SpanContext, SpanId, TraceContextExt as _, TraceFlags, TraceId, TraceState,
+        Tracer as _,
+    },
+    Context,
+};
+use pretty_assertions::assert_eq;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn remote_parent_is_root() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let tracer = opentelemetry_stdout_tree::new_pipeline()
+        .with_shared_writer(output.clone())
+        .with_terminal_width(60)
+        .with_timing_column_width(0.0)
+        .install_simple();
+    let take_output = || String::from_utf8(std::mem::take(&mut *output.lock().unwrap())).unwrap();
+
+    let remote = Context::new().with_remote_span_context(SpanContext::new(
+        TraceId::from_bytes(1u128.to_be_bytes()),
+        SpanId::from_bytes(2u64.to_be_bytes()),
+        TraceFlags::SAMPLED,
+        true,
+        TraceState::default(),
+    ));
+    let cx = remote.with_span(tracer.start_with_context("handler", &remote));
+    tracer.start_with_context("child", &cx).end();
+    // The trace is printed once the span with the remote parent ends, without a placeholder.
+    cx.span().end();
+    assert_eq!(
+        "IN  handler  remote parent 0000000000000002         0      0\n \
+         IN  child                                          0      0\n",
+        take_output()
+    );
+
+    // A span, whose parent hasn't ended, is grouped under a placeholder in the same trace.
+    let cx = remote.with_span(tracer.start_with_context("handler", &remote));
+    let pending = cx.with_span(tracer.start_with_context("pending", &cx));
+    let pending_id = pending.span().span_context().span_id();
+    tracer.start_with_context("orphan", &pending).end();
+    cx.span().end();
+    assert_eq!(
+        format!(
+            "incomplete trace: some parent spans have not been exported …\n\
+             IN  handler  remote parent 0000000000000002         0      0\n\
+             IN  {:016x}  parent not exported           0      0\n \
+             IN  orphan                                         0      0\n",
+            pending_id
+        ),
+        take_output()
+    );
+
+    global::shutdown_tracer_provider();
+}
@@ -0,0 +1,35 @@
+use opentelemetry::{
+    global,
+    trace::{SpanKind, Tracer as _},
+};
+use pretty_assertions::assert_eq;
+use std::sync::{Arc, Mutex};
+
+#[test]
+fn shared_writer() {
+    let output = Arc::new(Mutex::new(Vec::new()));
+    let tracer = opentelemetry_stdout_tree::new_pipeline()
+        .with_shared_writer(output.clone())
+        .with_terminal_width(60)
+        .with_timing_column_width(0.0)
+        .install_simple();
+
+    let span = tracer
+        .span_builder("root")
+        .with_kind(SpanKind::Server)
+        .start(&tracer);
+    tracer.with_span(span, |_cx| {
+        tracer.in_span("child", |_cx| {});
+    });
+
+    global::shutdown_tracer_provider();
+
+    let output = String::from_utf8(output.lock().unwrap().clone()).unwrap();
+    assert_eq!(
+        vec![
+            "SE  root                                            0      0",
+            " IN  child                                          0      0",
+        ],
+        output.lines().collect::<Vec<_>>()
+    );
+}